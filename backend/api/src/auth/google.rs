@@ -1,20 +1,25 @@
-use crate::auth::models::User;
-use crate::auth::types::AuthContext;
-use crate::auth::utils::normal_url_to_redirector;
-use crate::Database;
+use crate::auth::SESSION_USER_ID_KEY;
 use axum::extract::Query;
+use axum::http::StatusCode;
 use axum::response::{IntoResponse, Redirect, Response};
 use axum::routing::get;
 use axum::{Extension, Router};
-use axum_login::axum_sessions::extractors::{ReadableSession, WritableSession};
-use axum_sessions::extractors::{ReadableSession, WritableSession};
-use migration::DbErr;
-use openidconnect::core::{CoreClient, CoreResponseType, CoreUserInfoClaims};
+use axum_sessions::async_session::serde_json;
+use axum_sessions::extractors::WritableSession;
+use entity::sea_orm_active_enums::UserRole;
+use openidconnect::core::{CoreClient, CoreGenderClaim, CoreResponseType};
 use openidconnect::reqwest::async_http_client;
-use openidconnect::{AuthorizationCode, CsrfToken, Nonce, OAuth2TokenResponse, Scope};
-use serde::Deserialize;
+use openidconnect::{
+    AdditionalClaims, AuthorizationCode, CsrfToken, Nonce, OAuth2TokenResponse, Scope,
+    UserInfoClaims,
+};
+use serde::{Deserialize, Serialize};
+use std::env;
+use svelte_rust_event_scheduler_service::sea_orm::{DatabaseConnection, DbErr};
 use svelte_rust_event_scheduler_service::{Mutation, UserToCreate};
-use tracing::{info, trace};
+use tracing::{info, trace, warn};
+
+const SESSION_CSRF_STATE_KEY: &str = "csrf_state";
 
 pub fn google_router() -> Router {
     Router::new()
@@ -28,28 +33,88 @@ pub struct AuthRequest {
     state: CsrfToken,
 }
 
+/// Claims Google adds to the standard ones
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GoogleClaims {
+    /// The hosted domain of the account, only set for Google Workspace accounts
+    hd: Option<String>,
+}
+
+impl AdditionalClaims for GoogleClaims {}
+
+type GoogleUserInfoClaims = UserInfoClaims<GoogleClaims, CoreGenderClaim>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GoogleCallbackError {
+    #[error("Invalid CSRF state, please try to log in again")]
+    InvalidCsrfState,
+
+    #[error("Failed to exchange the authorization code: {0}")]
+    CodeExchange(String),
+
+    #[error("Failed to get the user info: {0}")]
+    UserInfo(String),
+
+    #[error("The account has no email address")]
+    MissingEmail,
+
+    #[error("The email address {0} is not verified")]
+    EmailNotVerified(String),
+
+    #[error(
+        "The account {0} is not part of an allowed domain, please log in with your school account"
+    )]
+    DomainNotAllowed(String),
+
+    #[error("The account {0} has been deactivated")]
+    Deactivated(String),
+
+    #[error("Failed to save the user")]
+    DatabaseError(#[from] DbErr),
+
+    #[error("Failed to save the session")]
+    Session(#[from] serde_json::Error),
+}
+
+impl IntoResponse for GoogleCallbackError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            GoogleCallbackError::InvalidCsrfState | GoogleCallbackError::MissingEmail => {
+                StatusCode::BAD_REQUEST
+            }
+            GoogleCallbackError::EmailNotVerified(_)
+            | GoogleCallbackError::DomainNotAllowed(_)
+            | GoogleCallbackError::Deactivated(_) => StatusCode::FORBIDDEN,
+            GoogleCallbackError::CodeExchange(_) | GoogleCallbackError::UserInfo(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            GoogleCallbackError::DatabaseError(_) | GoogleCallbackError::Session(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        (status, self.to_string()).into_response()
+    }
+}
+
 pub(super) async fn google_oauth_callback_handler(
-    mut auth: AuthContext,
     Query(query): Query<AuthRequest>,
     Extension(oauth_client): Extension<CoreClient>,
-    session: ReadableSession,
-) -> impl IntoResponse {
-    info!("Running oauth callback {query:?}");
+    Extension(db): Extension<DatabaseConnection>,
+    mut session: WritableSession,
+) -> Result<Redirect, GoogleCallbackError> {
+    info!("Running oauth callback");
     // Compare the csrf state in the callback with the state generated before the
     // request
-    let Some(original_csrf_state): Option<CsrfToken> = session.get("csrf_state") else {
-        return Redirect::to("/auth/google/login");
-    };
-    let query_csrf_state = query.state.secret();
-    let csrf_state_equal = original_csrf_state.secret() == query_csrf_state;
-
-    drop(session);
-
-    if !csrf_state_equal {
-        info!("csrf state is invalid, cannot login",);
-
-        //TODO: Return to some error
-        return Redirect::to("/auth/login-success");
+    let original_csrf_state: Option<CsrfToken> = session.get(SESSION_CSRF_STATE_KEY);
+    session.remove(SESSION_CSRF_STATE_KEY);
+
+    match original_csrf_state {
+        Some(original_csrf_state) if original_csrf_state.secret() == query.state.secret() => {}
+        _ => {
+            info!("csrf state is invalid, cannot login");
+            return Err(GoogleCallbackError::InvalidCsrfState);
+        }
     }
 
     info!("Getting oauth token");
@@ -58,51 +123,73 @@ pub(super) async fn google_oauth_callback_handler(
         .exchange_code(AuthorizationCode::new(query.code))
         .request_async(async_http_client)
         .await
-        .unwrap();
+        .map_err(|e| GoogleCallbackError::CodeExchange(e.to_string()))?;
 
-    let profile: CoreUserInfoClaims = oauth_client
+    let profile: GoogleUserInfoClaims = oauth_client
         .user_info(token.access_token().clone(), None)
-        .unwrap()
+        .map_err(|e| GoogleCallbackError::UserInfo(e.to_string()))?
         .request_async(async_http_client)
         .await
-        .unwrap();
+        .map_err(|e| GoogleCallbackError::UserInfo(e.to_string()))?;
 
-    let email = profile.email().unwrap().to_string();
+    let email = profile
+        .email()
+        .ok_or(GoogleCallbackError::MissingEmail)?
+        .to_string();
     trace!("Got email: {}", email);
 
-    oauth_client
-        .revoke_token(token.access_token().into())
-        .unwrap()
-        .request_async(async_http_client)
-        .await
-        .expect("Failed to revoke token");
+    if profile.email_verified() == Some(false) {
+        return Err(GoogleCallbackError::EmailNotVerified(email));
+    }
 
-    info!("Getting db connection");
+    if !is_domain_allowed(&email, profile.additional_claims().hd.as_deref()) {
+        info!("Refusing to log in {email}, its domain is not allowed");
+        return Err(GoogleCallbackError::DomainNotAllowed(email));
+    }
+
+    // The token is not needed anymore, not every provider supports revocation
+    if let Ok(revocation_request) = oauth_client.revoke_token(token.access_token().into()) {
+        if let Err(e) = revocation_request.request_async(async_http_client).await {
+            warn!("Failed to revoke token: {e}");
+        }
+    }
+
+    let name = profile
+        .name()
+        .and_then(|name| name.get(None))
+        .map(|name| name.to_string());
 
     info!("Getting user");
     let user = Mutation::add_user(
-        &pool,
+        &db,
         UserToCreate {
-            name: profile.name().unwrap().to_string(),
+            name,
             email,
             section: 0,
             class: "".to_string(),
-            admin: false,
+            role: UserRole::Student,
         },
     )
     .await?;
+    if !user.active {
+        info!("Refusing to log in {}, it has been deactivated", user.email);
+        return Err(GoogleCallbackError::Deactivated(user.email));
+    }
     info!("Got user {:?}. Logging in.", user.name);
-    auth.login(&user).await.unwrap();
+
+    // Prevent session fixation, the session id changes once the user is logged in
+    session.regenerate();
+    session.insert(SESSION_USER_ID_KEY, user.id)?;
 
     info!("Logged in the user: {user:?}");
 
-    Redirect::to("/auth/login-success")
+    Ok(Redirect::to(&login_redirect_url()))
 }
 
 pub(super) async fn google_login_handler(
     Extension(client): Extension<CoreClient>,
     mut session: WritableSession,
-) -> impl IntoResponse {
+) -> Result<Redirect, GoogleCallbackError> {
     let (authorize_url, csrf_state, _nonce) = client
         .authorize_url(
             openidconnect::AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
@@ -110,15 +197,46 @@ pub(super) async fn google_login_handler(
             Nonce::new_random,
         )
         .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
         .url();
 
     // Store the csrf_state in the session, so we can assert equality in the callback
-    session.insert("csrf_state", csrf_state).unwrap();
+    session.insert(SESSION_CSRF_STATE_KEY, csrf_state)?;
 
     // Redirect to your oauth service
-    Redirect::to(normal_url_to_redirector(authorize_url.as_str()).as_str())
+    Ok(Redirect::to(authorize_url.as_str()))
 }
 
-fn normal_url_to_redirector(url: &str) -> String {
-    todo!()
+/// Where to send the user once the login is completed, defaults to the root of the site
+fn login_redirect_url() -> String {
+    env::var("LOGIN_REDIRECT_URL").unwrap_or("/".to_string())
+}
+
+/// The domains allowed to log in, from the comma-separated `EMAIL_DOMAIN` environment variable.
+/// When it is not set every domain is allowed.
+fn allowed_email_domains() -> Vec<String> {
+    env::var("EMAIL_DOMAIN")
+        .unwrap_or_default()
+        .split(',')
+        .map(|domain| domain.trim().to_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .collect()
+}
+
+/// Checks both the domain of the email and the hosted domain (`hd`) claim,
+/// as a personal Google account can be registered with any email address
+fn is_domain_allowed(email: &str, hosted_domain: Option<&str>) -> bool {
+    let allowed_domains = allowed_email_domains();
+
+    if allowed_domains.is_empty() {
+        return true;
+    }
+
+    let Some((_, email_domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+
+    let is_allowed = |domain: &str| allowed_domains.contains(&domain.to_lowercase());
+
+    is_allowed(email_domain) && hosted_domain.is_some_and(is_allowed)
 }
@@ -1,3 +1,11 @@
+use crate::auth::token::JwtKeys;
+use axum::body::Body;
+use axum::http::header::AUTHORIZATION;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Extension;
+use axum_sessions::SessionHandle;
 use openidconnect::core::{
     CoreAuthDisplay, CoreClaimName, CoreClaimType, CoreClient, CoreClientAuthMethod, CoreGrantType,
     CoreJsonWebKey, CoreJsonWebKeyType, CoreJsonWebKeyUse, CoreJweContentEncryptionAlgorithm,
@@ -10,12 +18,24 @@ use openidconnect::{
     RevocationUrl,
 };
 use serde::{Deserialize, Serialize};
+use std::env;
+use svelte_rust_event_scheduler_service::sea_orm::DatabaseConnection;
+use svelte_rust_event_scheduler_service::Query;
+use tracing::{info, warn};
 
-mod google;
+pub mod google;
+pub mod token;
+
+/// The issuer used when `OIDC_ISSUER_URL` is not set
+const GOOGLE_ISSUER_URL: &str = "https://accounts.google.com";
+
+/// Session key holding the id of the logged-in user
+pub const SESSION_USER_ID_KEY: &str = "user_id";
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 struct RevocationEndpointProviderMetadata {
-    revocation_endpoint: String,
+    // Not every OpenID Connect provider (e.g. local mock issuers) advertises one
+    revocation_endpoint: Option<String>,
 }
 
 impl AdditionalProviderMetadata for RevocationEndpointProviderMetadata {}
@@ -38,30 +58,97 @@ type GoogleProviderMetadata = ProviderMetadata<
     CoreSubjectIdentifierType,
 >;
 
-pub async fn build_google_oauth_client(secret: &SecretStore, redirect_uri: String) -> CoreClient {
-    let client_id = secret
-        .get("GOOGLE_CLIENT_ID")
-        .expect("Missing GOOGLE_CLIENT_ID!");
-    let client_id = ClientId::new(client_id.to_string());
-    let client_secret = secret
-        .get("GOOGLE_CLIENT_SECRET")
-        .expect("Missing GOOGLE_CLIENT_SECRET!");
-    let client_secret = ClientSecret::new(client_secret.to_string());
-    let issuer_url =
-        IssuerUrl::new("https://accounts.google.com".to_string()).expect("Invalid issuer URL");
+#[derive(Debug, thiserror::Error)]
+pub enum BuildOAuthClientError {
+    #[error("Missing environment variable {0}")]
+    MissingVar(&'static str),
+
+    #[error("Invalid URL in {0}")]
+    InvalidUrl(&'static str),
+
+    #[error("Failed to discover the OpenID Connect provider metadata: {0}")]
+    Discovery(String),
+}
+
+/// Builds the OpenID Connect client used to log in with Google.
+///
+/// The issuer defaults to Google, but can be pointed to any OpenID Connect provider
+/// (e.g. a local mock issuer) through the `OIDC_ISSUER_URL` environment variable.
+pub async fn build_google_oauth_client() -> Result<CoreClient, BuildOAuthClientError> {
+    let client_id = env::var("GOOGLE_CLIENT_ID")
+        .map_err(|_| BuildOAuthClientError::MissingVar("GOOGLE_CLIENT_ID"))?;
+    let client_id = ClientId::new(client_id);
+    let client_secret = env::var("GOOGLE_CLIENT_SECRET")
+        .map_err(|_| BuildOAuthClientError::MissingVar("GOOGLE_CLIENT_SECRET"))?;
+    let client_secret = ClientSecret::new(client_secret);
+    let redirect_url = env::var("GOOGLE_REDIRECT_URL")
+        .map_err(|_| BuildOAuthClientError::MissingVar("GOOGLE_REDIRECT_URL"))?;
+    let redirect_url = RedirectUrl::new(redirect_url)
+        .map_err(|_| BuildOAuthClientError::InvalidUrl("GOOGLE_REDIRECT_URL"))?;
+
+    let issuer_url = env::var("OIDC_ISSUER_URL").unwrap_or(GOOGLE_ISSUER_URL.to_string());
+    let issuer_url = IssuerUrl::new(issuer_url)
+        .map_err(|_| BuildOAuthClientError::InvalidUrl("OIDC_ISSUER_URL"))?;
 
     let provider_metadata = GoogleProviderMetadata::discover_async(issuer_url, async_http_client)
         .await
-        .expect("Failed to discover Google provider metadata");
+        .map_err(|e| BuildOAuthClientError::Discovery(e.to_string()))?;
 
     let revocation_endpoint = provider_metadata
         .additional_metadata()
         .revocation_endpoint
         .clone();
 
-    CoreClient::from_provider_metadata(provider_metadata, client_id, Some(client_secret))
-        .set_redirect_uri(RedirectUrl::new(redirect_uri).expect("Invalid redirect URL"))
-        .set_revocation_uri(
-            RevocationUrl::new(revocation_endpoint).expect("Invalid revocation endpoint URL"),
-        )
+    let client =
+        CoreClient::from_provider_metadata(provider_metadata, client_id, Some(client_secret))
+            .set_redirect_uri(redirect_url);
+
+    let client = match revocation_endpoint {
+        Some(revocation_endpoint) => client.set_revocation_uri(
+            RevocationUrl::new(revocation_endpoint)
+                .map_err(|_| BuildOAuthClientError::InvalidUrl("revocation_endpoint"))?,
+        ),
+        None => client,
+    };
+
+    Ok(client)
+}
+
+/// Resolves the user calling the service, from either a bearer token or the session cookie,
+/// and inserts its `user::Model` into the request extensions.
+///
+/// Requests without a valid identity (or of a deactivated user) are let through, `check_auth_interceptor` rejects them.
+pub async fn identify_user(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(jwt_keys): Extension<JwtKeys>,
+    mut request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let bearer_user_id = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| jwt_keys.verify(token));
+
+    let user_id = match bearer_user_id {
+        Some(user_id) => Some(user_id),
+        None => match request.extensions().get::<SessionHandle>() {
+            Some(session) => session.read().await.get::<i32>(SESSION_USER_ID_KEY),
+            None => None,
+        },
+    };
+
+    if let Some(user_id) = user_id {
+        match Query::get_user_by_id(&db, user_id).await {
+            Ok(Some(user)) if user.active => {
+                request.extensions_mut().insert(user);
+            }
+            Ok(Some(_)) => info!("Ignoring the identity of the deactivated user {user_id}"),
+            Ok(None) => warn!("Got a valid identity for the missing user {user_id}"),
+            Err(e) => warn!("Failed to get the user {user_id}: {e}"),
+        }
+    }
+
+    next.run(request).await
 }
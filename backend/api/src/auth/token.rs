@@ -0,0 +1,96 @@
+use crate::auth::SESSION_USER_ID_KEY;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use axum_sessions::extractors::ReadableSession;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long an issued token is valid for
+const TOKEN_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Keys used to sign and verify the HS256 tokens, derived from `AUTH_SECRET`
+#[derive(Clone)]
+pub struct JwtKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// The id of the user the token was issued to
+    sub: i32,
+    exp: u64,
+}
+
+impl JwtKeys {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    /// Issues a token for the given user
+    pub fn issue(&self, user_id: i32) -> Result<String, jsonwebtoken::errors::Error> {
+        let expires_at = SystemTime::now() + TOKEN_TTL;
+
+        let claims = Claims {
+            sub: user_id,
+            exp: expires_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+
+        jsonwebtoken::encode(&Header::default(), &claims, &self.encoding)
+    }
+
+    /// Verifies the signature and expiration of a token, returning the id of its user
+    pub fn verify(&self, token: &str) -> Option<i32> {
+        jsonwebtoken::decode::<Claims>(token, &self.decoding, &Validation::default())
+            .map(|data| data.claims.sub)
+            .ok()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    token: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("Not logged in")]
+    NotLoggedIn,
+
+    #[error("Failed to issue the token")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+}
+
+impl IntoResponse for TokenError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            TokenError::NotLoggedIn => StatusCode::UNAUTHORIZED,
+            TokenError::Jwt(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Issues a token for the user logged in the current session,
+/// to be sent as `authorization: Bearer <token>` to the gRPC service
+pub async fn token_handler(
+    Extension(jwt_keys): Extension<JwtKeys>,
+    session: ReadableSession,
+) -> Result<Json<TokenResponse>, TokenError> {
+    let user_id: i32 = session
+        .get(SESSION_USER_ID_KEY)
+        .ok_or(TokenError::NotLoggedIn)?;
+
+    let token = jwt_keys.issue(user_id)?;
+
+    Ok(Json(TokenResponse { token }))
+}
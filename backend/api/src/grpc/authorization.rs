@@ -0,0 +1,33 @@
+use entity::sea_orm_active_enums::UserRole;
+use entity::user;
+use tonic::Request;
+
+/// Returns the user that `identify_user` resolved for the request
+pub(super) fn authenticated_user<T>(request: &Request<T>) -> Option<user::Model> {
+    request.extensions().get::<user::Model>().cloned()
+}
+
+/// Admins can do everything
+pub(super) fn is_admin(user: &user::Model) -> bool {
+    user.role == UserRole::Admin
+}
+
+/// Staff (teachers and admins) can look at who attends an event and mark attendance
+pub(super) fn is_staff(user: &user::Model) -> bool {
+    matches!(user.role, UserRole::Teacher | UserRole::Admin)
+}
+
+/// Returns the id of the user a request acts on: the caller itself,
+/// or the given user when the caller is allowed to act on its behalf
+pub(super) fn target_user_id(
+    caller: &user::Model,
+    on_behalf_of_user_id: Option<i32>,
+    can_act_on_behalf: fn(&user::Model) -> bool,
+) -> Option<i32> {
+    match on_behalf_of_user_id {
+        None => Some(caller.id),
+        Some(user_id) if user_id == caller.id => Some(user_id),
+        Some(user_id) if can_act_on_behalf(caller) => Some(user_id),
+        Some(_) => None,
+    }
+}
@@ -1,7 +1,16 @@
-use crate::grpc::event_scheduler::{EventUsersStatusResponse, EventsResponse};
+use crate::grpc::event_scheduler::{
+    self, student_notification, EventUsersStatusResponse, EventsResponse, StudentNotification,
+};
 use axum_sessions::async_session::chrono::NaiveDateTime;
 use entity::event::Model as Event;
-use svelte_rust_event_scheduler_service::EventUserStatus;
+use entity::sea_orm_active_enums::UserRole;
+use entity::{edition, registration_window, round, user};
+use svelte_rust_event_scheduler_service::{
+    AllocatedSeat, AssignedSeat, BumpedStudent, CancelledSubscription, CapacityFilter,
+    EventCapacity, EventColumns, EventImportRowError, EventRoundSeats, EventSubscription,
+    EventToSave, EventUserStatus, RosterImportRowError, RoundColumn, UnallocatedSeat,
+    WaitlistPromotion,
+};
 
 macro_rules! impl_from {
     ($src:ident, $dst:ident, $($field:ident),*) => {
@@ -9,6 +18,7 @@ macro_rules! impl_from {
             fn from(item: $src) -> Self {
                 Self {
                     $( $field: item.$field, )*
+                    ..Default::default()
                 }
             }
         }
@@ -23,9 +33,211 @@ impl_from!(
     room,
     zone,
     floor,
-    minimum_section
+    minimum_section,
+    round_span
 );
 
+impl From<EventRoundSeats> for event_scheduler::EventRoundSeats {
+    fn from(item: EventRoundSeats) -> Self {
+        Self {
+            round: item.round,
+            max_users: item.max_users,
+            registered: item.registered,
+            remaining: (item.max_users.max(0) as u64).saturating_sub(item.registered),
+        }
+    }
+}
+
+impl From<round::Model> for event_scheduler::Round {
+    fn from(item: round::Model) -> Self {
+        Self {
+            id: item.id,
+            day: item.day.format("%Y-%m-%d").to_string(),
+            start_time: item.start_time.format("%H:%M").to_string(),
+            end_time: item.end_time.format("%H:%M").to_string(),
+            label: item.label,
+        }
+    }
+}
+
+impl From<event_scheduler::EventFields> for EventToSave {
+    fn from(item: event_scheduler::EventFields) -> Self {
+        Self {
+            name: item.name,
+            room: item.room,
+            zone: item.zone,
+            floor: item.floor,
+            minimum_section: item.minimum_section,
+            round_span: item.round_span,
+        }
+    }
+}
+
+impl From<CancelledSubscription> for event_scheduler::CancelledSubscription {
+    fn from(item: CancelledSubscription) -> Self {
+        Self {
+            user_id: item.user_id,
+            round: item.round,
+        }
+    }
+}
+
+impl From<event_scheduler::CapacityFilter> for CapacityFilter {
+    fn from(item: event_scheduler::CapacityFilter) -> Self {
+        Self {
+            event_ids: item.event_ids,
+            name_contains: item.name_contains,
+            rounds: item.rounds,
+        }
+    }
+}
+
+impl From<EventCapacity> for event_scheduler::EventCapacity {
+    fn from(item: EventCapacity) -> Self {
+        Self {
+            event_id: item.event_id,
+            event_name: item.event_name,
+            round: item.round,
+            max_users: item.max_users,
+            registered: item.registered,
+        }
+    }
+}
+
+impl From<BumpedStudent> for event_scheduler::BumpedStudent {
+    fn from(item: BumpedStudent) -> Self {
+        Self {
+            user_id: item.user_id,
+            round: item.round,
+            event_id: item.event_id,
+        }
+    }
+}
+
+impl From<BumpedStudent> for StudentNotification {
+    fn from(item: BumpedStudent) -> Self {
+        Self {
+            notification: Some(student_notification::Notification::SeatRevoked(
+                event_scheduler::SeatRevoked {
+                    round: item.round,
+                    event_id: item.event_id,
+                },
+            )),
+        }
+    }
+}
+
+impl From<event_scheduler::EventColumns> for EventColumns {
+    fn from(item: event_scheduler::EventColumns) -> Self {
+        Self {
+            name: item.name,
+            room: item.room,
+            zone: item.zone,
+            floor: item.floor,
+            minimum_section: item.minimum_section,
+            round_span: item.round_span,
+            max_users: item.max_users,
+            turn: item.turn,
+            rounds: item
+                .rounds
+                .into_iter()
+                .map(|round| RoundColumn {
+                    round: round.round,
+                    column: round.column,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<EventImportRowError> for event_scheduler::EventImportRowError {
+    fn from(item: EventImportRowError) -> Self {
+        Self {
+            row: item.row,
+            column: item.column,
+            message: item.message,
+        }
+    }
+}
+
+impl From<RosterImportRowError> for event_scheduler::RosterImportRowError {
+    fn from(item: RosterImportRowError) -> Self {
+        Self {
+            row: item.row,
+            message: item.message,
+        }
+    }
+}
+
+impl From<edition::Model> for event_scheduler::Edition {
+    fn from(item: edition::Model) -> Self {
+        Self {
+            id: item.id,
+            name: item.name,
+            active: item.active,
+            archived_at: item.archived_at.map(convert_naive_date_time_to_timestamp),
+        }
+    }
+}
+
+impl From<registration_window::Model> for event_scheduler::ConfiguredRegistrationWindow {
+    fn from(item: registration_window::Model) -> Self {
+        Self {
+            id: item.id,
+            edition_id: item.edition_id,
+            window: Some(event_scheduler::RegistrationWindowFields {
+                section: item.section,
+                class: item.class,
+                opens_at: Some(convert_naive_date_time_to_timestamp(item.opens_at)),
+                closes_at: Some(convert_naive_date_time_to_timestamp(item.closes_at)),
+            }),
+        }
+    }
+}
+
+impl From<UserRole> for event_scheduler::UserRole {
+    fn from(item: UserRole) -> Self {
+        match item {
+            UserRole::Student => Self::Student,
+            UserRole::Teacher => Self::Teacher,
+            UserRole::Admin => Self::Admin,
+        }
+    }
+}
+
+impl From<event_scheduler::UserRole> for UserRole {
+    fn from(item: event_scheduler::UserRole) -> Self {
+        match item {
+            event_scheduler::UserRole::Student => Self::Student,
+            event_scheduler::UserRole::Teacher => Self::Teacher,
+            event_scheduler::UserRole::Admin => Self::Admin,
+        }
+    }
+}
+
+impl From<user::Model> for event_scheduler::User {
+    fn from(item: user::Model) -> Self {
+        Self {
+            id: item.id,
+            name: item.name,
+            email: item.email,
+            section: item.section,
+            class: item.class,
+            role: event_scheduler::UserRole::from(item.role).into(),
+            active: item.active,
+        }
+    }
+}
+
+impl From<EventSubscription> for event_scheduler::EventSubscription {
+    fn from(item: EventSubscription) -> Self {
+        Self {
+            round: item.round,
+            event_id: item.event_id,
+        }
+    }
+}
+
 impl From<EventUserStatus> for EventUsersStatusResponse {
     fn from(item: EventUserStatus) -> Self {
         Self {
@@ -40,7 +252,51 @@ impl From<EventUserStatus> for EventUsersStatusResponse {
     }
 }
 
-fn convert_naive_date_time_to_timestamp(item: NaiveDateTime) -> prost_types::Timestamp {
+impl From<WaitlistPromotion> for StudentNotification {
+    fn from(item: WaitlistPromotion) -> Self {
+        Self {
+            notification: Some(student_notification::Notification::WaitlistPromotion(
+                event_scheduler::WaitlistPromotion {
+                    round: item.round,
+                    event_id: item.event_id,
+                    previous_event_id: item.previous_event_id,
+                },
+            )),
+        }
+    }
+}
+
+impl From<AllocatedSeat> for event_scheduler::AllocatedSeat {
+    fn from(item: AllocatedSeat) -> Self {
+        Self {
+            user_id: item.user_id,
+            round: item.round,
+            event_id: item.event_id,
+            rank: item.rank,
+        }
+    }
+}
+
+impl From<AssignedSeat> for event_scheduler::AssignedSeat {
+    fn from(item: AssignedSeat) -> Self {
+        Self {
+            user_id: item.user_id,
+            round: item.round,
+            event_id: item.event_id,
+        }
+    }
+}
+
+impl From<UnallocatedSeat> for event_scheduler::UnallocatedSeat {
+    fn from(item: UnallocatedSeat) -> Self {
+        Self {
+            user_id: item.user_id,
+            round: item.round,
+        }
+    }
+}
+
+pub(super) fn convert_naive_date_time_to_timestamp(item: NaiveDateTime) -> prost_types::Timestamp {
     prost_types::Timestamp {
         seconds: item.timestamp(),
         nanos: 0,
@@ -1,30 +1,48 @@
+mod authorization;
 mod entity_response_conversion;
-
-use axum::Router;
+mod subscriber_counts;
+
+use axum::routing::get;
+use axum::{middleware, Extension, Router};
+use axum_sessions::async_session::chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use axum_sessions::async_session::MemoryStore;
+use axum_sessions::{PersistencePolicy, SameSite, SessionLayer};
+use entity::{registration_window, user};
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use crate::auth::google::google_router;
+use crate::auth::token::{token_handler, JwtKeys};
+use crate::auth::{build_google_oauth_client, identify_user, BuildOAuthClientError};
+use crate::grpc::authorization::{authenticated_user, is_admin, is_staff, target_user_id};
+use crate::grpc::entity_response_conversion::convert_naive_date_time_to_timestamp;
 use crate::grpc::event_scheduler::schedule_service_server::ScheduleServiceServer;
 use crate::grpc::event_scheduler::{
-    EventUsersStatusRequest, EventUsersStatusResponse, EventsResponse, SubscriberCountStreamUpdate,
+    student_notification, EventUsersStatusRequest, EventUsersStatusResponse, EventsResponse,
+    RegistrationMode, StudentNotification, SubscriberCountStreamUpdate, UserRole,
 };
+use crate::grpc::subscriber_counts::SubscriberCounts;
 use event_scheduler::schedule_service_server::ScheduleService;
-use futures::future::join_all;
 use migration::{Migrator, MigratorTrait};
 use svelte_rust_event_scheduler_service::{
     sea_orm,
     sea_orm::{Database, DatabaseConnection},
-    Mutation, Query,
+    Allocation, AssignmentOptions, CapacityError, CapacityFilter, CapacityOptions, EditionError,
+    EventDeletePolicy, EventError, EventImport, EventImportError, EventImportOptions,
+    EventSubscription, Mutation, NewEdition, NewRound, PreferencesError, Query,
+    RegistrationWindowToSave, RosterImport, RosterImportError, RosterImportOptions,
+    RoundPreferences, Sections, SubscribeError, SubscriptionRules, UserError, UserSearch,
+    UserUpdate, WaitlistError, WaitlistPromotion, WindowError,
 };
 use tokio::sync::mpsc::Sender;
 use tokio::sync::{mpsc, Mutex};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::codegen::tokio_stream;
 use tonic::codegen::tokio_stream::{Stream, StreamExt};
-use tonic::metadata::MetadataValue;
 use tonic::{Request, Response, Status};
-use tracing::info;
+use tracing::{debug, info};
 
 #[derive(Debug, thiserror::Error)]
 pub enum StartServerError {
@@ -40,6 +58,18 @@ pub enum StartServerError {
     #[error("Failed to run migrations")]
     Migration(#[from] migration::error::Error),
 
+    #[error("Failed to build the OpenID Connect client")]
+    OAuthClient(#[from] BuildOAuthClientError),
+
+    #[error("AUTH_SECRET must be at least 64 bytes long")]
+    AuthSecretTooShort,
+
+    #[error("REGISTRATION_MODE must be either first_come or preferences, got {0}")]
+    InvalidRegistrationMode(String),
+
+    #[error("MAX_SECTION must be a positive integer, got {0}")]
+    InvalidMaxSection(String),
+
     #[error("Failed to start the server")]
     Axum(#[from] axum::Error),
 
@@ -63,19 +93,56 @@ pub async fn start_server() -> Result<(), StartServerError> {
     let db = Database::connect(db_url).await?;
     Migrator::up(&db, None).await?;
 
+    let auth_secret = env::var("AUTH_SECRET")?;
+    if auth_secret.len() < 64 {
+        return Err(StartServerError::AuthSecretTooShort);
+    }
+
+    let registration_mode = registration_mode_from_env()?;
+    info!("Registration mode: {}", registration_mode.as_str_name());
+
+    let subscription_rules = subscription_rules_from_env();
+    info!("Subscription rules: {:?}", subscription_rules);
+
+    let sections = sections_from_env()?;
+    info!("Sections: 1 to {}", sections.max);
+
+    let oauth_client = build_google_oauth_client().await?;
+
+    // The session cookie must be sent back when the OpenID Connect provider redirects
+    // to the callback, which a strict same site policy would prevent
+    #[allow(deprecated)]
+    let session_layer = SessionLayer::new(MemoryStore::new(), auth_secret.as_bytes())
+        .with_same_site_policy(SameSite::Lax)
+        .with_persistence_policy(PersistencePolicy::ChangedOnly);
+
+    let jwt_keys = JwtKeys::new(auth_secret.as_bytes());
+
     let schedule_service = MyScheduleService {
-        database: db,
-        ..Default::default()
+        subscriber_counts: SubscriberCounts::spawn(db.clone()),
+        students: Default::default(),
+        database: db.clone(),
+        registration_mode,
+        subscription_rules,
+        sections,
     };
 
     let schedule_service_server =
         ScheduleServiceServer::with_interceptor(schedule_service, check_auth_interceptor);
 
     let schedule_service_server = tonic_web::enable(schedule_service_server);
-    let app = Router::new().route(
-        "/online.polp.schedule_service.ScheduleService/*rpc",
-        axum::routing::any_service(schedule_service_server.clone()),
-    );
+    let app = Router::new()
+        .route(
+            "/online.polp.schedule_service.ScheduleService/*rpc",
+            axum::routing::any_service(schedule_service_server.clone()),
+        )
+        .route_layer(middleware::from_fn(identify_user))
+        .route("/auth/token", get(token_handler))
+        .nest("/auth/google", google_router())
+        .layer(Extension(jwt_keys))
+        .layer(Extension(oauth_client))
+        .layer(Extension(db))
+        .layer(session_layer);
 
     info!("Service will listen on {}", addr);
 
@@ -86,21 +153,83 @@ pub async fn start_server() -> Result<(), StartServerError> {
     Ok(())
 }
 
-/// Uses openid connect to authenticate the user
+/// How the students get their seats, from the `REGISTRATION_MODE` environment variable
+/// (`first_come` when not set)
+fn registration_mode_from_env() -> Result<RegistrationMode, StartServerError> {
+    match env::var("REGISTRATION_MODE") {
+        Ok(mode) => match mode.as_str() {
+            "first_come" => Ok(RegistrationMode::FirstCome),
+            "preferences" => Ok(RegistrationMode::Preferences),
+            _ => Err(StartServerError::InvalidRegistrationMode(mode)),
+        },
+        Err(_) => Ok(RegistrationMode::FirstCome),
+    }
+}
+
+/// The rules of the subscriptions, repeated events are allowed only when
+/// `ALLOW_REPEATED_EVENTS` is `true`
+fn subscription_rules_from_env() -> SubscriptionRules {
+    SubscriptionRules {
+        allow_repeated_events: env::var("ALLOW_REPEATED_EVENTS").is_ok_and(|v| v == "true"),
+    }
+}
+
+/// The sections of the school, from 1 to the `MAX_SECTION` environment variable
+/// (`DEFAULT_MAX_SECTION` when not set)
+fn sections_from_env() -> Result<Sections, StartServerError> {
+    match env::var("MAX_SECTION") {
+        Ok(max) => match max.parse() {
+            Ok(max) if max > 0 => Ok(Sections { max }),
+            _ => Err(StartServerError::InvalidMaxSection(max)),
+        },
+        Err(_) => Ok(Sections::default()),
+    }
+}
+
+/// Rejects the requests for which `identify_user` could not resolve the calling user,
+/// either from a bearer token or from the session cookie
+#[allow(clippy::result_large_err)]
 fn check_auth_interceptor(request: Request<()>) -> Result<Request<()>, Status> {
-    let token: MetadataValue<_> = "Bearer some-secret-token".parse().unwrap();
+    match request.extensions().get::<user::Model>() {
+        Some(_) => Ok(request),
+        None => Err(Status::unauthenticated("No valid auth token or session")),
+    }
+}
 
-    match request.metadata().get("authorization") {
-        Some(t) if token == t => Ok(request),
-        _ => Err(Status::unauthenticated("No valid auth token")),
+/// Whether subscriptions can be changed now, registrations are always open without a window
+fn is_registration_open(window: Option<&registration_window::Model>) -> bool {
+    match window {
+        Some(window) => {
+            let now = Utc::now().naive_utc();
+            window.opens_at <= now && now < window.closes_at
+        }
+        None => true,
     }
 }
 
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum SubscriberCountError {
+    #[error("Failed to get the subscriber counts")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum RoundsError {
+    #[error("Failed to get the rounds")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+}
+
 #[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
 enum ResponseStreamEventsError {
     #[error("Failed to get events")]
     #[code(Internal)]
     DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 }
 
 #[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
@@ -108,208 +237,2125 @@ enum EventSubscriptionError {
     #[error("Failed to subscribe to event")]
     #[code(Internal)]
     DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only admins can act on behalf of another user")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("Invalid rounds: {0}")]
+    #[code(InvalidArgument)]
+    InvalidRounds(String),
+
+    #[error("User {0} not found")]
+    #[code(NotFound)]
+    UserNotFound(i32),
+
+    #[error("Registrations are closed")]
+    #[code(FailedPrecondition)]
+    RegistrationsClosed,
+
+    #[error("Registrations use ranked preferences, submit them instead")]
+    #[code(FailedPrecondition)]
+    PreferencesMode,
+
+    #[error("The section of the user is too low for the following events: {0:?}")]
+    #[code(FailedPrecondition)]
+    NotEligible(Vec<i32>),
+
+    #[error("The following events are full: {0:?}")]
+    #[code(ResourceExhausted)]
+    EventsFull(Vec<EventSubscription>),
+
+    #[error("The following events can't be picked in more than one round: {0:?}")]
+    #[code(InvalidArgument)]
+    RepeatedEvents(Vec<i32>),
+
+    #[error("The following multi-round events start too late to fit in the rounds: {0:?}")]
+    #[code(InvalidArgument)]
+    RoundSpanPastLastRound(Vec<i32>),
+
+    #[error("The following subscriptions overlap with the rounds of multi-round events: {0:?}")]
+    #[code(InvalidArgument)]
+    OverlappingRounds(Vec<EventSubscription>),
+}
+
+impl From<SubscribeError> for EventSubscriptionError {
+    fn from(err: SubscribeError) -> Self {
+        match err {
+            SubscribeError::Database(err) => Self::DatabaseError(err),
+            SubscribeError::InvalidRounds(reason) => Self::InvalidRounds(reason),
+            SubscribeError::UserNotFound(user_id) => Self::UserNotFound(user_id),
+            SubscribeError::NotEligible(event_ids) => Self::NotEligible(event_ids),
+            SubscribeError::EventsFull(subscriptions) => Self::EventsFull(subscriptions),
+            SubscribeError::RepeatedEvents(event_ids) => Self::RepeatedEvents(event_ids),
+            SubscribeError::RoundSpanPastLastRound(event_ids) => {
+                Self::RoundSpanPastLastRound(event_ids)
+            }
+            SubscribeError::OverlappingRounds(subscriptions) => {
+                Self::OverlappingRounds(subscriptions)
+            }
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
-enum EventJoinResponseError {
-    #[error("Failed to join event")]
+enum WaitlistJoinError {
+    #[error("Failed to join the waitlist")]
     #[code(Internal)]
     DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Registrations are closed")]
+    #[code(FailedPrecondition)]
+    RegistrationsClosed,
+
+    #[error("Registrations use ranked preferences, there are no waitlists")]
+    #[code(FailedPrecondition)]
+    PreferencesMode,
+
+    #[error("Round {0} doesn't exist")]
+    #[code(InvalidArgument)]
+    InvalidRound(i32),
+
+    #[error("User {0} not found")]
+    #[code(NotFound)]
+    UserNotFound(i32),
+
+    #[error("{0}")]
+    #[code(FailedPrecondition)]
+    NotAllowed(String),
+}
+
+impl From<WaitlistError> for WaitlistJoinError {
+    fn from(err: WaitlistError) -> Self {
+        match err {
+            WaitlistError::Database(err) => Self::DatabaseError(err),
+            WaitlistError::InvalidRound(round) => Self::InvalidRound(round),
+            WaitlistError::UserNotFound(user_id) => Self::UserNotFound(user_id),
+            err @ (WaitlistError::NotEligible
+            | WaitlistError::NotRunning
+            | WaitlistError::NotFull
+            | WaitlistError::AlreadySubscribed
+            | WaitlistError::RepeatedEvent
+            | WaitlistError::MultiRound
+            | WaitlistError::InMultiRoundEvent) => Self::NotAllowed(err.to_string()),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
-enum EventLeaveResponseError {
-    #[error("Failed to leave event")]
+enum WaitlistLeaveError {
+    #[error("Failed to leave the waitlist")]
     #[code(Internal)]
     DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 }
 
 #[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
-enum ResponseStreamEventUsersStatusError {
-    #[error("Failed to get event users status")]
+enum StudentNotificationsError {
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum PreferencesSubmitError {
+    #[error("Failed to save the preferences")]
     #[code(Internal)]
     DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only admins can act on behalf of another user")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("Registrations are first come first served, subscribe to the events instead")]
+    #[code(FailedPrecondition)]
+    FirstComeMode,
+
+    #[error("Registrations are closed")]
+    #[code(FailedPrecondition)]
+    RegistrationsClosed,
+
+    #[error("Invalid preferences: {0}")]
+    #[code(InvalidArgument)]
+    InvalidPreferences(String),
+
+    #[error("User {0} not found")]
+    #[code(NotFound)]
+    UserNotFound(i32),
+
+    #[error("The section of the user is too low for the following events: {0:?}")]
+    #[code(FailedPrecondition)]
+    NotEligible(Vec<i32>),
+
+    #[error("The following events don't run in their round: {0:?}")]
+    #[code(FailedPrecondition)]
+    NotRunning(Vec<EventSubscription>),
 }
 
-type ResponseStreamSubscriberCount =
-    Pin<Box<dyn Stream<Item = Result<SubscriberCountStreamUpdate, Status>> + Send>>;
+impl From<PreferencesError> for PreferencesSubmitError {
+    fn from(err: PreferencesError) -> Self {
+        match err {
+            PreferencesError::Database(err) => Self::DatabaseError(err),
+            PreferencesError::InvalidPreferences(reason) => Self::InvalidPreferences(reason),
+            PreferencesError::UserNotFound(user_id) => Self::UserNotFound(user_id),
+            PreferencesError::NotEligible(event_ids) => Self::NotEligible(event_ids),
+            PreferencesError::NotRunning(subscriptions) => Self::NotRunning(subscriptions),
+        }
+    }
+}
 
-type ResponseStreamEvents = Pin<Box<dyn Stream<Item = Result<EventsResponse, Status>> + Send>>;
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum PreferencesQueryError {
+    #[error("Failed to get the preferences")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-type ResponseStreamEventUsersStatus =
-    Pin<Box<dyn Stream<Item = Result<EventUsersStatusResponse, Status>> + Send>>;
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-pub mod event_scheduler {
-    tonic::include_proto!("online.polp.schedule_service");
+    #[error("Only admins can act on behalf of another user")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
 }
 
-type SubscribersToNotify = Vec<Sender<SubscriberCountStreamUpdate>>;
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum AllocationError {
+    #[error("Failed to allocate the seats")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-#[derive(Default)]
-pub struct MyScheduleService {
-    subscribers: Arc<Mutex<SubscribersToNotify>>,
-    database: DatabaseConnection,
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only admins can allocate the seats")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("Registrations are first come first served, there are no preferences to allocate")]
+    #[code(FailedPrecondition)]
+    FirstComeMode,
 }
 
-impl MyScheduleService {
-    pub async fn notify_subscribers(&self, update: SubscriberCountStreamUpdate) {
-        let subscribers = self.subscribers.lock().await;
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum AssignUnregisteredError {
+    #[error("Failed to assign the unregistered students")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-        let mut futures = Vec::with_capacity(subscribers.len());
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-        for subscriber in subscribers.iter() {
-            futures.push(subscriber.send(update.clone()));
-        }
+    #[error("Only admins can assign the unregistered students")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum EventChangeError {
+    #[error("Failed to save the event")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only admins can manage the events")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("Invalid event: {0}")]
+    #[code(InvalidArgument)]
+    InvalidEvent(String),
 
-        join_all(futures).await;
+    #[error("Event {0} not found")]
+    #[code(NotFound)]
+    NotFound(i32),
+
+    #[error("An event named {0:?} already exists")]
+    #[code(AlreadyExists)]
+    DuplicateName(String),
+
+    #[error("There is no active edition to add the event to")]
+    #[code(FailedPrecondition)]
+    NoActiveEdition,
+
+    #[error("{0} students are subscribed to the event, delete it with cascade to remove them too")]
+    #[code(FailedPrecondition)]
+    HasSubscriptions(u64),
+
+    #[error("Students are subscribed to the event, the rounds it lasts can't be changed")]
+    #[code(FailedPrecondition)]
+    RoundSpanInUse,
+}
+
+impl From<EventError> for EventChangeError {
+    fn from(err: EventError) -> Self {
+        match err {
+            EventError::Database(err) => Self::DatabaseError(err),
+            EventError::InvalidEvent(reason) => Self::InvalidEvent(reason),
+            EventError::NotFound(event_id) => Self::NotFound(event_id),
+            EventError::DuplicateName(name) => Self::DuplicateName(name),
+            EventError::NoActiveEdition => Self::NoActiveEdition,
+            EventError::HasSubscriptions(count) => Self::HasSubscriptions(count),
+            EventError::RoundSpanInUse => Self::RoundSpanInUse,
+        }
     }
 }
 
-#[tonic::async_trait]
-impl ScheduleService for MyScheduleService {
-    async fn ping(
-        &self,
-        request: Request<event_scheduler::PingRequest>,
-    ) -> Result<Response<event_scheduler::PingReply>, Status> {
-        println!("Got a ping from {:?}", request.remote_addr());
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum CapacityChangeError {
+    #[error("Failed to save the capacities")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-        let reply = event_scheduler::PingReply {
-            message: "Pong!".to_string(),
-        };
-        Ok(Response::new(reply))
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only admins can manage the capacities")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("Invalid capacity: {0}")]
+    #[code(InvalidArgument)]
+    InvalidCapacity(String),
+}
+
+impl From<CapacityError> for CapacityChangeError {
+    fn from(err: CapacityError) -> Self {
+        match err {
+            CapacityError::Database(err) => Self::DatabaseError(err),
+            CapacityError::NegativeCapacity(_)
+            | CapacityError::NoEventsSelected
+            | CapacityError::UnknownRound(_) => Self::InvalidCapacity(err.to_string()),
+        }
     }
+}
 
-    type SubscriberCountStream = ResponseStreamSubscriberCount;
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum ImportEventsError {
+    #[error("Failed to import the events")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-    async fn subscriber_count(
-        &self,
-        _request: Request<event_scheduler::SubscriberCountRequest>,
-    ) -> Result<Response<Self::SubscriberCountStream>, Status> {
-        let (tx, rx) = mpsc::channel(4);
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-        self.subscribers.lock().await.push(tx);
+    #[error("Only admins can import the events")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
 
-        let output_stream = ReceiverStream::new(rx).map(Ok::<_, Status>);
-        Ok(Response::new(
-            Box::pin(output_stream) as Self::SubscriberCountStream
-        ))
-    }
+    #[error("The column mapping is missing")]
+    #[code(InvalidArgument)]
+    MissingColumns,
 
-    type EventsStream = ResponseStreamEvents;
+    #[error("Invalid workbook: {0}")]
+    #[code(InvalidArgument)]
+    InvalidWorkbook(String),
 
-    async fn events(
-        &self,
-        request: Request<event_scheduler::EventsRequest>,
-    ) -> Result<Response<Self::EventsStream>, Status> {
-        self.events_impl(request).await.map_err(|e| e.into())
-    }
+    #[error("There is no active edition to import the events into")]
+    #[code(FailedPrecondition)]
+    NoActiveEdition,
+}
 
-    async fn subscribe_to_events(
-        &self,
-        request: Request<event_scheduler::EventSubscriptionRequest>,
-    ) -> Result<Response<event_scheduler::EventSubscriptionResponse>, Status> {
-        self.subscribe_to_events_impl(request)
-            .await
-            .map_err(|e| e.into())
+impl From<EventImportError> for ImportEventsError {
+    fn from(err: EventImportError) -> Self {
+        match err {
+            EventImportError::Database(err) => Self::DatabaseError(err),
+            EventImportError::NoActiveEdition => Self::NoActiveEdition,
+            EventImportError::Workbook(_)
+            | EventImportError::SheetNotFound(_)
+            | EventImportError::MissingColumn(_)
+            | EventImportError::UnknownRound(_)
+            | EventImportError::DuplicateRound(_) => Self::InvalidWorkbook(err.to_string()),
+        }
     }
+}
 
-    async fn join_event(
-        &self,
-        request: Request<event_scheduler::EventJoinRequest>,
-    ) -> Result<Response<event_scheduler::EventJoinResponse>, Status> {
-        self.join_event_impl(request).await.map_err(|e| e.into())
-    }
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum ImportRosterError {
+    #[error("Failed to import the roster")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-    async fn leave_event(
-        &self,
-        request: Request<event_scheduler::EventLeaveRequest>,
-    ) -> Result<Response<event_scheduler::EventLeaveResponse>, Status> {
-        self.leave_event_impl(request).await.map_err(|e| e.into())
-    }
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-    type EventUsersStatusStream = ResponseStreamEventUsersStatus;
+    #[error("Only admins can import the roster")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
 
-    async fn event_users_status(
-        &self,
-        request: Request<EventUsersStatusRequest>,
-    ) -> Result<Response<Self::EventUsersStatusStream>, Status> {
-        self.event_users_status_impl(request)
-            .await
-            .map_err(|e| e.into())
+    #[error("Invalid roster: {0}")]
+    #[code(InvalidArgument)]
+    InvalidRoster(String),
+}
+
+impl From<RosterImportError> for ImportRosterError {
+    fn from(err: RosterImportError) -> Self {
+        match err {
+            RosterImportError::Database(err) => Self::DatabaseError(err),
+            RosterImportError::Csv(_) | RosterImportError::MissingColumn(_) => {
+                Self::InvalidRoster(err.to_string())
+            }
+        }
     }
 }
 
-impl MyScheduleService {
-    async fn events_impl(
-        &self,
-        _request: Request<event_scheduler::EventsRequest>,
-    ) -> Result<Response<ResponseStreamEvents>, ResponseStreamEventsError> {
-        let events = Query::get_all_events(&self.database).await?;
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum UsersError {
+    #[error("Failed to search the users")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-        let events = events.into_iter().map(|event| {
-            let event: EventsResponse = event.into();
-            event
-        });
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-        let output_stream = tokio_stream::iter(events.into_iter().map(Ok::<_, Status>));
+    #[error("Only admins can search the users")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
 
-        Ok(Response::new(
-            Box::pin(output_stream) as ResponseStreamEvents
-        ))
-    }
+    #[error("Invalid role {0}")]
+    #[code(InvalidArgument)]
+    InvalidRole(i32),
+}
 
-    async fn subscribe_to_events_impl(
-        &self,
-        request: Request<event_scheduler::EventSubscriptionRequest>,
-    ) -> Result<Response<event_scheduler::EventSubscriptionResponse>, EventSubscriptionError> {
-        let body = request.into_inner();
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum UserChangeError {
+    #[error("Failed to save the user")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-        info!(
-            "User {} subscribed to events {:?}",
-            body.user_id, body.event_ids
-        );
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-        Mutation::subscribe_to_events(&self.database, body.user_id, &body.event_ids).await?;
+    #[error("Only admins can manage the users")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
 
-        let counts = Query::get_events_user_count_by_ids(&self.database, body.event_ids).await?;
+    #[error("Invalid user: {0}")]
+    #[code(InvalidArgument)]
+    InvalidUser(String),
 
-        for count in counts {
-            self.notify_subscribers(SubscriberCountStreamUpdate {
-                id: count.event_id,
-                subscriber_count: count.count,
-            })
-            .await;
-        }
+    #[error("User {0} not found")]
+    #[code(NotFound)]
+    NotFound(i32),
 
-        Ok(Response::new(event_scheduler::EventSubscriptionResponse {}))
+    #[error("Admins can't demote, deactivate or delete their own account")]
+    #[code(FailedPrecondition)]
+    OwnAccount,
+}
+
+impl From<UserError> for UserChangeError {
+    fn from(err: UserError) -> Self {
+        match err {
+            UserError::Database(err) => Self::DatabaseError(err),
+            UserError::NotFound(user_id) => Self::NotFound(user_id),
+            UserError::InvalidSection(..) => Self::InvalidUser(err.to_string()),
+        }
     }
+}
 
-    async fn join_event_impl(
-        &self,
-        request: Request<event_scheduler::EventJoinRequest>,
-    ) -> Result<Response<event_scheduler::EventJoinResponse>, EventJoinResponseError> {
-        let body = request.into_inner();
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum EditionsError {
+    #[error("Failed to get the editions")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-        Mutation::join_event(&self.database, body.user_id, body.event_id).await?;
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-        Ok(Response::new(event_scheduler::EventJoinResponse {}))
-    }
+    #[error("Only admins can manage the editions")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+}
 
-    async fn leave_event_impl(
-        &self,
-        request: Request<event_scheduler::EventLeaveRequest>,
-    ) -> Result<Response<event_scheduler::EventLeaveResponse>, EventLeaveResponseError> {
-        let body = request.into_inner();
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum EditionChangeError {
+    #[error("Failed to save the edition")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
 
-        Mutation::leave_event(&self.database, body.user_id, body.event_id).await?;
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
 
-        Ok(Response::new(event_scheduler::EventLeaveResponse {}))
+    #[error("Only admins can manage the editions")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("Invalid round: {0}")]
+    #[code(InvalidArgument)]
+    InvalidRound(String),
+
+    #[error("Edition {0} not found")]
+    #[code(NotFound)]
+    NotFound(i32),
+
+    #[error("Edition {0} is archived")]
+    #[code(FailedPrecondition)]
+    Archived(i32),
+
+    #[error("Edition {0} is the active one, activate another edition before archiving it")]
+    #[code(FailedPrecondition)]
+    Active(i32),
+}
+
+/// Parses the day (YYYY-MM-DD) and the times (HH:MM) of a round
+fn parse_new_round(round: event_scheduler::NewRound) -> Result<NewRound, EditionChangeError> {
+    let invalid = |field: &str, value: &str| {
+        EditionChangeError::InvalidRound(format!("invalid {field} {value:?} for {:?}", round.label))
+    };
+
+    let day = NaiveDate::parse_from_str(&round.day, "%Y-%m-%d")
+        .map_err(|_| invalid("day", &round.day))?;
+    let start_time = NaiveTime::parse_from_str(&round.start_time, "%H:%M")
+        .map_err(|_| invalid("start time", &round.start_time))?;
+    let end_time = NaiveTime::parse_from_str(&round.end_time, "%H:%M")
+        .map_err(|_| invalid("end time", &round.end_time))?;
+
+    Ok(NewRound {
+        day,
+        start_time,
+        end_time,
+        label: round.label,
+    })
+}
+
+impl From<EditionError> for EditionChangeError {
+    fn from(err: EditionError) -> Self {
+        match err {
+            EditionError::Database(err) => Self::DatabaseError(err),
+            EditionError::NotFound(edition_id) => Self::NotFound(edition_id),
+            EditionError::Archived(edition_id) => Self::Archived(edition_id),
+            EditionError::Active(edition_id) => Self::Active(edition_id),
+            EditionError::InvalidRound(label) => {
+                Self::InvalidRound(format!("{label:?} ends before it starts"))
+            }
+            EditionError::RoundCount { .. } => Self::InvalidRound(err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum RegistrationWindowError {
+    #[error("Failed to get the registration window")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum RegistrationWindowsError {
+    #[error("Failed to get the registration windows")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only staff can manage the registration windows")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum RegistrationWindowChangeError {
+    #[error("Failed to save the registration window")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only staff can manage the registration windows")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("Invalid registration window: {0}")]
+    #[code(InvalidArgument)]
+    InvalidWindow(String),
+
+    #[error("Registration window {0} not found")]
+    #[code(NotFound)]
+    NotFound(i32),
+
+    #[error("Edition {0} not found")]
+    #[code(NotFound)]
+    EditionNotFound(i32),
+
+    #[error("Edition {0} is archived")]
+    #[code(FailedPrecondition)]
+    ArchivedEdition(i32),
+
+    #[error("There is no active edition to add the registration window to")]
+    #[code(FailedPrecondition)]
+    NoActiveEdition,
+}
+
+impl From<WindowError> for RegistrationWindowChangeError {
+    fn from(err: WindowError) -> Self {
+        match err {
+            WindowError::Database(err) => Self::DatabaseError(err),
+            WindowError::InvalidWindow(reason) => Self::InvalidWindow(reason),
+            WindowError::NotFound(window_id) => Self::NotFound(window_id),
+            WindowError::EditionNotFound(edition_id) => Self::EditionNotFound(edition_id),
+            WindowError::ArchivedEdition(edition_id) => Self::ArchivedEdition(edition_id),
+            WindowError::NoActiveEdition => Self::NoActiveEdition,
+        }
+    }
+}
+
+/// Reads the fields of a registration window, both of its times are required
+fn parse_registration_window(
+    window: Option<event_scheduler::RegistrationWindowFields>,
+) -> Result<RegistrationWindowToSave, RegistrationWindowChangeError> {
+    let window = window.ok_or(RegistrationWindowChangeError::InvalidWindow(
+        "The window is missing".to_string(),
+    ))?;
+
+    let date_time = |timestamp: Option<prost_types::Timestamp>, field: &str| {
+        timestamp
+            .and_then(|t| DateTime::from_timestamp(t.seconds, t.nanos.try_into().ok()?))
+            .map(|date_time| date_time.naive_utc())
+            .ok_or_else(|| {
+                RegistrationWindowChangeError::InvalidWindow(format!(
+                    "The {field} time is missing or invalid"
+                ))
+            })
+    };
+
+    Ok(RegistrationWindowToSave {
+        section: window.section,
+        class: window.class,
+        opens_at: date_time(window.opens_at, "opening")?,
+        closes_at: date_time(window.closes_at, "closing")?,
+    })
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum EventJoinResponseError {
+    #[error("Failed to join event")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only staff can mark attendance")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("The student whose attendance to mark is missing")]
+    #[code(InvalidArgument)]
+    MissingUser,
+
+    #[error("User {0} is not subscribed to event {1} in round {2}")]
+    #[code(NotFound)]
+    NotSubscribed(i32, i32, i32),
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum EventLeaveResponseError {
+    #[error("Failed to leave event")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only staff can mark attendance")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+
+    #[error("The student whose attendance to mark is missing")]
+    #[code(InvalidArgument)]
+    MissingUser,
+
+    #[error("User {0} is not subscribed to event {1} in round {2}")]
+    #[code(NotFound)]
+    NotSubscribed(i32, i32, i32),
+}
+
+#[derive(Debug, thiserror::Error, tonic_thiserror::TonicThisError)]
+enum ResponseStreamEventUsersStatusError {
+    #[error("Failed to get event users status")]
+    #[code(Internal)]
+    DatabaseError(#[from] sea_orm::error::DbErr),
+
+    #[error("Not authenticated")]
+    #[code(Unauthenticated)]
+    Unauthenticated,
+
+    #[error("Only staff can see the event users status")]
+    #[code(PermissionDenied)]
+    PermissionDenied,
+}
+
+type ResponseStreamSubscriberCount =
+    Pin<Box<dyn Stream<Item = Result<SubscriberCountStreamUpdate, Status>> + Send>>;
+
+type ResponseStreamStudentNotifications =
+    Pin<Box<dyn Stream<Item = Result<StudentNotification, Status>> + Send>>;
+
+type ResponseStreamEvents = Pin<Box<dyn Stream<Item = Result<EventsResponse, Status>> + Send>>;
+
+type ResponseStreamEventUsersStatus =
+    Pin<Box<dyn Stream<Item = Result<EventUsersStatusResponse, Status>> + Send>>;
+
+// `UserInfoStatus` is part of the public API but not used by the server yet
+#[allow(dead_code)]
+pub mod event_scheduler {
+    tonic::include_proto!("online.polp.schedule_service");
+}
+
+/// The notifications kept for a student stream that isn't read, it is closed once full
+const STUDENT_NOTIFICATIONS_BUFFER: usize = 16;
+
+/// The notification streams of every connected student, by user id
+type StudentsToNotify = HashMap<i32, Vec<Sender<StudentNotification>>>;
+
+pub struct MyScheduleService {
+    subscriber_counts: SubscriberCounts,
+    students: Arc<Mutex<StudentsToNotify>>,
+    database: DatabaseConnection,
+    registration_mode: RegistrationMode,
+    subscription_rules: SubscriptionRules,
+    sections: Sections,
+}
+
+impl MyScheduleService {
+    /// Sends the current subscriber count of the given events to every subscriber
+    pub fn notify_subscriber_counts(&self, event_ids: Vec<i32>) {
+        self.subscriber_counts.notify(event_ids);
+    }
+
+    /// Sends the notification to every stream of the student without waiting on them, a stream
+    /// that doesn't keep up is closed and the student has to reconnect
+    pub async fn notify_student(&self, user_id: i32, notification: StudentNotification) {
+        let mut students = self.students.lock().await;
+
+        let Some(senders) = students.get_mut(&user_id) else {
+            return;
+        };
+
+        senders.retain(|sender| sender.try_send(notification.clone()).is_ok());
+
+        if senders.is_empty() {
+            students.remove(&user_id);
+        }
+    }
+
+    pub async fn notify_promotions(&self, promotions: &[WaitlistPromotion]) {
+        for promotion in promotions {
+            info!(
+                "User {} was promoted from the waitlist to event {} in round {}",
+                promotion.user_id, promotion.event_id, promotion.round
+            );
+
+            self.notify_student(promotion.user_id, (*promotion).into())
+                .await;
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ScheduleService for MyScheduleService {
+    async fn ping(
+        &self,
+        request: Request<event_scheduler::PingRequest>,
+    ) -> Result<Response<event_scheduler::PingReply>, Status> {
+        debug!("Got a ping from {:?}", request.remote_addr());
+
+        let reply = event_scheduler::PingReply {
+            message: "Pong!".to_string(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    type SubscriberCountStream = ResponseStreamSubscriberCount;
+
+    async fn subscriber_count(
+        &self,
+        request: Request<event_scheduler::SubscriberCountRequest>,
+    ) -> Result<Response<Self::SubscriberCountStream>, Status> {
+        self.subscriber_count_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn rounds(
+        &self,
+        request: Request<event_scheduler::RoundsRequest>,
+    ) -> Result<Response<event_scheduler::RoundsResponse>, Status> {
+        self.rounds_impl(request).await.map_err(|e| e.into())
+    }
+
+    type EventsStream = ResponseStreamEvents;
+
+    async fn events(
+        &self,
+        request: Request<event_scheduler::EventsRequest>,
+    ) -> Result<Response<Self::EventsStream>, Status> {
+        self.events_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn subscribe_to_events(
+        &self,
+        request: Request<event_scheduler::EventSubscriptionRequest>,
+    ) -> Result<Response<event_scheduler::EventSubscriptionResponse>, Status> {
+        self.subscribe_to_events_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn join_waitlist(
+        &self,
+        request: Request<event_scheduler::WaitlistJoinRequest>,
+    ) -> Result<Response<event_scheduler::WaitlistJoinResponse>, Status> {
+        self.join_waitlist_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn leave_waitlist(
+        &self,
+        request: Request<event_scheduler::WaitlistLeaveRequest>,
+    ) -> Result<Response<event_scheduler::WaitlistLeaveResponse>, Status> {
+        self.leave_waitlist_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    type StudentNotificationsStream = ResponseStreamStudentNotifications;
+
+    async fn student_notifications(
+        &self,
+        request: Request<event_scheduler::StudentNotificationsRequest>,
+    ) -> Result<Response<Self::StudentNotificationsStream>, Status> {
+        self.student_notifications_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn submit_preferences(
+        &self,
+        request: Request<event_scheduler::PreferencesSubmitRequest>,
+    ) -> Result<Response<event_scheduler::PreferencesSubmitResponse>, Status> {
+        self.submit_preferences_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn preferences(
+        &self,
+        request: Request<event_scheduler::PreferencesRequest>,
+    ) -> Result<Response<event_scheduler::PreferencesResponse>, Status> {
+        self.preferences_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn allocate_preferences(
+        &self,
+        request: Request<event_scheduler::AllocationRequest>,
+    ) -> Result<Response<event_scheduler::AllocationResponse>, Status> {
+        self.allocate_preferences_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn assign_unregistered(
+        &self,
+        request: Request<event_scheduler::AssignUnregisteredRequest>,
+    ) -> Result<Response<event_scheduler::AssignUnregisteredResponse>, Status> {
+        self.assign_unregistered_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn create_event(
+        &self,
+        request: Request<event_scheduler::EventCreateRequest>,
+    ) -> Result<Response<event_scheduler::EventCreateResponse>, Status> {
+        self.create_event_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn update_event(
+        &self,
+        request: Request<event_scheduler::EventUpdateRequest>,
+    ) -> Result<Response<event_scheduler::EventUpdateResponse>, Status> {
+        self.update_event_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn delete_event(
+        &self,
+        request: Request<event_scheduler::EventDeleteRequest>,
+    ) -> Result<Response<event_scheduler::EventDeleteResponse>, Status> {
+        self.delete_event_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn capacities(
+        &self,
+        request: Request<event_scheduler::CapacitiesRequest>,
+    ) -> Result<Response<event_scheduler::CapacitiesResponse>, Status> {
+        self.capacities_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn set_capacities(
+        &self,
+        request: Request<event_scheduler::CapacitySetRequest>,
+    ) -> Result<Response<event_scheduler::CapacitySetResponse>, Status> {
+        self.set_capacities_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn import_events(
+        &self,
+        request: Request<event_scheduler::EventImportRequest>,
+    ) -> Result<Response<event_scheduler::EventImportResponse>, Status> {
+        self.import_events_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn import_roster(
+        &self,
+        request: Request<event_scheduler::RosterImportRequest>,
+    ) -> Result<Response<event_scheduler::RosterImportResponse>, Status> {
+        self.import_roster_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn search_users(
+        &self,
+        request: Request<event_scheduler::UserSearchRequest>,
+    ) -> Result<Response<event_scheduler::UserSearchResponse>, Status> {
+        self.search_users_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn update_user(
+        &self,
+        request: Request<event_scheduler::UserUpdateRequest>,
+    ) -> Result<Response<event_scheduler::UserUpdateResponse>, Status> {
+        self.update_user_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn set_user_active(
+        &self,
+        request: Request<event_scheduler::UserActiveSetRequest>,
+    ) -> Result<Response<event_scheduler::UserActiveSetResponse>, Status> {
+        self.set_user_active_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn delete_user(
+        &self,
+        request: Request<event_scheduler::UserDeleteRequest>,
+    ) -> Result<Response<event_scheduler::UserDeleteResponse>, Status> {
+        self.delete_user_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn editions(
+        &self,
+        request: Request<event_scheduler::EditionsRequest>,
+    ) -> Result<Response<event_scheduler::EditionsResponse>, Status> {
+        self.editions_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn create_edition(
+        &self,
+        request: Request<event_scheduler::EditionCreateRequest>,
+    ) -> Result<Response<event_scheduler::EditionCreateResponse>, Status> {
+        self.create_edition_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn activate_edition(
+        &self,
+        request: Request<event_scheduler::EditionActivateRequest>,
+    ) -> Result<Response<event_scheduler::EditionActivateResponse>, Status> {
+        self.activate_edition_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn archive_edition(
+        &self,
+        request: Request<event_scheduler::EditionArchiveRequest>,
+    ) -> Result<Response<event_scheduler::EditionArchiveResponse>, Status> {
+        self.archive_edition_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn registration_window(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowRequest>,
+    ) -> Result<Response<event_scheduler::RegistrationWindowResponse>, Status> {
+        self.registration_window_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn registration_windows(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowsRequest>,
+    ) -> Result<Response<event_scheduler::RegistrationWindowsResponse>, Status> {
+        self.registration_windows_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn create_registration_window(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowCreateRequest>,
+    ) -> Result<Response<event_scheduler::RegistrationWindowCreateResponse>, Status> {
+        self.create_registration_window_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn update_registration_window(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowUpdateRequest>,
+    ) -> Result<Response<event_scheduler::RegistrationWindowUpdateResponse>, Status> {
+        self.update_registration_window_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn delete_registration_window(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowDeleteRequest>,
+    ) -> Result<Response<event_scheduler::RegistrationWindowDeleteResponse>, Status> {
+        self.delete_registration_window_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    async fn join_event(
+        &self,
+        request: Request<event_scheduler::EventJoinRequest>,
+    ) -> Result<Response<event_scheduler::EventJoinResponse>, Status> {
+        self.join_event_impl(request).await.map_err(|e| e.into())
+    }
+
+    async fn leave_event(
+        &self,
+        request: Request<event_scheduler::EventLeaveRequest>,
+    ) -> Result<Response<event_scheduler::EventLeaveResponse>, Status> {
+        self.leave_event_impl(request).await.map_err(|e| e.into())
+    }
+
+    type EventUsersStatusStream = ResponseStreamEventUsersStatus;
+
+    async fn event_users_status(
+        &self,
+        request: Request<EventUsersStatusRequest>,
+    ) -> Result<Response<Self::EventUsersStatusStream>, Status> {
+        self.event_users_status_impl(request)
+            .await
+            .map_err(|e| e.into())
+    }
+}
+
+impl MyScheduleService {
+    async fn subscriber_count_impl(
+        &self,
+        _request: Request<event_scheduler::SubscriberCountRequest>,
+    ) -> Result<Response<ResponseStreamSubscriberCount>, SubscriberCountError> {
+        let output_stream = self.subscriber_counts.subscribe().await?;
+
+        Ok(Response::new(
+            Box::pin(output_stream) as ResponseStreamSubscriberCount
+        ))
+    }
+
+    async fn rounds_impl(
+        &self,
+        _request: Request<event_scheduler::RoundsRequest>,
+    ) -> Result<Response<event_scheduler::RoundsResponse>, RoundsError> {
+        let rounds = Query::get_rounds(&self.database).await?;
+
+        Ok(Response::new(event_scheduler::RoundsResponse {
+            rounds: rounds.into_iter().map(|round| round.into()).collect(),
+        }))
+    }
+
+    async fn events_impl(
+        &self,
+        request: Request<event_scheduler::EventsRequest>,
+    ) -> Result<Response<ResponseStreamEvents>, ResponseStreamEventsError> {
+        let caller =
+            authenticated_user(&request).ok_or(ResponseStreamEventsError::Unauthenticated)?;
+        let body = request.into_inner();
+
+        let events = Query::get_all_events(&self.database).await?;
+
+        let mut seats_by_event: HashMap<i32, Vec<event_scheduler::EventRoundSeats>> =
+            HashMap::new();
+        for seats in Query::get_events_seats(&self.database).await? {
+            seats_by_event
+                .entry(seats.event_id)
+                .or_default()
+                .push(seats.into());
+        }
+
+        let events = events
+            .into_iter()
+            .filter(|event| !body.only_eligible || event.minimum_section <= caller.section)
+            .map(|event| {
+                let eligible = event.minimum_section <= caller.section;
+                let rounds = seats_by_event.remove(&event.id).unwrap_or_default();
+                let mut event: EventsResponse = event.into();
+                event.eligible = eligible;
+                event.rounds = rounds;
+                event
+            })
+            .collect::<Vec<_>>();
+
+        let output_stream = tokio_stream::iter(events.into_iter().map(Ok::<_, Status>));
+
+        Ok(Response::new(
+            Box::pin(output_stream) as ResponseStreamEvents
+        ))
+    }
+
+    async fn subscribe_to_events_impl(
+        &self,
+        request: Request<event_scheduler::EventSubscriptionRequest>,
+    ) -> Result<Response<event_scheduler::EventSubscriptionResponse>, EventSubscriptionError> {
+        let caller = authenticated_user(&request).ok_or(EventSubscriptionError::Unauthenticated)?;
+        let body = request.into_inner();
+
+        let user_id = target_user_id(&caller, body.on_behalf_of_user_id, is_admin)
+            .ok_or(EventSubscriptionError::PermissionDenied)?;
+
+        // Admins can still fix the subscriptions of a student once registrations are closed,
+        // everyone else can only act on its own subscriptions
+        if !is_admin(&caller) {
+            if self.registration_mode == RegistrationMode::Preferences {
+                return Err(EventSubscriptionError::PreferencesMode);
+            }
+
+            let window = Query::registration_window_for_user(&self.database, &caller).await?;
+
+            if !is_registration_open(window.as_ref()) {
+                return Err(EventSubscriptionError::RegistrationsClosed);
+            }
+        }
+
+        let subscriptions = body
+            .subscriptions
+            .into_iter()
+            .map(|s| EventSubscription {
+                round: s.round,
+                event_id: s.event_id,
+            })
+            .collect::<Vec<_>>();
+
+        info!(
+            "User {} subscribed to events {:?} (by user {})",
+            user_id, subscriptions, caller.id
+        );
+
+        let changes = Mutation::subscribe_to_events(
+            &self.database,
+            user_id,
+            &subscriptions,
+            self.subscription_rules,
+        )
+        .await?;
+
+        self.notify_promotions(&changes.promotions).await;
+
+        // Also notify the events the user left
+        self.notify_subscriber_counts(changes.affected_event_ids());
+
+        Ok(Response::new(event_scheduler::EventSubscriptionResponse {}))
+    }
+
+    async fn join_waitlist_impl(
+        &self,
+        request: Request<event_scheduler::WaitlistJoinRequest>,
+    ) -> Result<Response<event_scheduler::WaitlistJoinResponse>, WaitlistJoinError> {
+        let caller = authenticated_user(&request).ok_or(WaitlistJoinError::Unauthenticated)?;
+        let body = request.into_inner();
+
+        if self.registration_mode == RegistrationMode::Preferences {
+            return Err(WaitlistJoinError::PreferencesMode);
+        }
+
+        let window = Query::registration_window_for_user(&self.database, &caller).await?;
+
+        if !is_registration_open(window.as_ref()) {
+            return Err(WaitlistJoinError::RegistrationsClosed);
+        }
+
+        let position = Mutation::join_waitlist(
+            &self.database,
+            caller.id,
+            EventSubscription {
+                round: body.round,
+                event_id: body.event_id,
+            },
+            self.subscription_rules,
+        )
+        .await?;
+
+        info!(
+            "User {} is waiting for event {} in round {} (position {})",
+            caller.id, body.event_id, body.round, position
+        );
+
+        Ok(Response::new(event_scheduler::WaitlistJoinResponse {
+            position,
+        }))
+    }
+
+    async fn leave_waitlist_impl(
+        &self,
+        request: Request<event_scheduler::WaitlistLeaveRequest>,
+    ) -> Result<Response<event_scheduler::WaitlistLeaveResponse>, WaitlistLeaveError> {
+        let caller = authenticated_user(&request).ok_or(WaitlistLeaveError::Unauthenticated)?;
+        let body = request.into_inner();
+
+        Mutation::leave_waitlist(&self.database, caller.id, body.round).await?;
+
+        Ok(Response::new(event_scheduler::WaitlistLeaveResponse {}))
+    }
+
+    async fn student_notifications_impl(
+        &self,
+        request: Request<event_scheduler::StudentNotificationsRequest>,
+    ) -> Result<Response<ResponseStreamStudentNotifications>, StudentNotificationsError> {
+        let caller =
+            authenticated_user(&request).ok_or(StudentNotificationsError::Unauthenticated)?;
+
+        let (tx, rx) = mpsc::channel(STUDENT_NOTIFICATIONS_BUFFER);
+
+        self.students
+            .lock()
+            .await
+            .entry(caller.id)
+            .or_default()
+            .push(tx);
+
+        let output_stream = ReceiverStream::new(rx).map(Ok::<_, Status>);
+        Ok(Response::new(
+            Box::pin(output_stream) as ResponseStreamStudentNotifications
+        ))
+    }
+
+    async fn submit_preferences_impl(
+        &self,
+        request: Request<event_scheduler::PreferencesSubmitRequest>,
+    ) -> Result<Response<event_scheduler::PreferencesSubmitResponse>, PreferencesSubmitError> {
+        let caller = authenticated_user(&request).ok_or(PreferencesSubmitError::Unauthenticated)?;
+        let body = request.into_inner();
+
+        if self.registration_mode != RegistrationMode::Preferences {
+            return Err(PreferencesSubmitError::FirstComeMode);
+        }
+
+        let user_id = target_user_id(&caller, body.on_behalf_of_user_id, is_admin)
+            .ok_or(PreferencesSubmitError::PermissionDenied)?;
+
+        if !is_admin(&caller) {
+            let window = Query::registration_window_for_user(&self.database, &caller).await?;
+
+            if !is_registration_open(window.as_ref()) {
+                return Err(PreferencesSubmitError::RegistrationsClosed);
+            }
+        }
+
+        let preferences = body
+            .rounds
+            .into_iter()
+            .map(|p| RoundPreferences {
+                round: p.round,
+                event_ids: p.event_ids,
+            })
+            .collect::<Vec<_>>();
+
+        info!(
+            "User {} ranked events {:?} (by user {})",
+            user_id, preferences, caller.id
+        );
+
+        Mutation::set_preferences(&self.database, user_id, &preferences).await?;
+
+        Ok(Response::new(event_scheduler::PreferencesSubmitResponse {}))
+    }
+
+    async fn preferences_impl(
+        &self,
+        request: Request<event_scheduler::PreferencesRequest>,
+    ) -> Result<Response<event_scheduler::PreferencesResponse>, PreferencesQueryError> {
+        let caller = authenticated_user(&request).ok_or(PreferencesQueryError::Unauthenticated)?;
+        let body = request.into_inner();
+
+        let user_id = target_user_id(&caller, body.on_behalf_of_user_id, is_admin)
+            .ok_or(PreferencesQueryError::PermissionDenied)?;
+
+        let preferences = Query::get_preferences_by_user_id(&self.database, user_id).await?;
+
+        // The preferences are sorted by round, then by rank
+        let mut rounds: Vec<event_scheduler::RoundPreferences> = Vec::new();
+
+        for preference in preferences {
+            match rounds.last_mut() {
+                Some(round) if round.round == preference.round => {
+                    round.event_ids.push(preference.event_id)
+                }
+                _ => rounds.push(event_scheduler::RoundPreferences {
+                    round: preference.round,
+                    event_ids: vec![preference.event_id],
+                }),
+            }
+        }
+
+        Ok(Response::new(event_scheduler::PreferencesResponse {
+            rounds,
+        }))
+    }
+
+    async fn allocate_preferences_impl(
+        &self,
+        request: Request<event_scheduler::AllocationRequest>,
+    ) -> Result<Response<event_scheduler::AllocationResponse>, AllocationError> {
+        let caller = authenticated_user(&request).ok_or(AllocationError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(AllocationError::PermissionDenied);
+        }
+
+        if self.registration_mode != RegistrationMode::Preferences {
+            return Err(AllocationError::FirstComeMode);
+        }
+
+        let body = request.into_inner();
+
+        let outcome =
+            Allocation::allocate_preferences(&self.database, body.seed, self.subscription_rules)
+                .await?;
+
+        info!(
+            "User {} allocated {} seats with seed {}, {} left unallocated",
+            caller.id,
+            outcome.allocated.len(),
+            outcome.seed,
+            outcome.unallocated.len()
+        );
+
+        let event_ids = Query::get_all_events(&self.database)
+            .await?
+            .into_iter()
+            .map(|event| event.id)
+            .collect();
+        self.notify_subscriber_counts(event_ids);
+
+        Ok(Response::new(event_scheduler::AllocationResponse {
+            seed: outcome.seed,
+            allocated: outcome.allocated.into_iter().map(|s| s.into()).collect(),
+            unallocated: outcome.unallocated.into_iter().map(|s| s.into()).collect(),
+        }))
+    }
+
+    async fn assign_unregistered_impl(
+        &self,
+        request: Request<event_scheduler::AssignUnregisteredRequest>,
+    ) -> Result<Response<event_scheduler::AssignUnregisteredResponse>, AssignUnregisteredError>
+    {
+        let caller =
+            authenticated_user(&request).ok_or(AssignUnregisteredError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(AssignUnregisteredError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+
+        let outcome = Allocation::assign_unregistered(
+            &self.database,
+            AssignmentOptions {
+                dry_run: body.dry_run,
+                keep_classmates_together: body.keep_classmates_together,
+                seed: body.seed,
+                rules: self.subscription_rules,
+            },
+        )
+        .await?;
+
+        info!(
+            "User {} assigned {} seats to unregistered students with seed {} (applied: {}), {} left unassigned",
+            caller.id,
+            outcome.assigned.len(),
+            outcome.seed,
+            outcome.applied,
+            outcome.unassigned.len()
+        );
+
+        if outcome.applied {
+            let mut event_ids = outcome
+                .assigned
+                .iter()
+                .map(|seat| seat.event_id)
+                .collect::<Vec<_>>();
+            event_ids.sort_unstable();
+            event_ids.dedup();
+
+            self.notify_subscriber_counts(event_ids);
+        }
+
+        Ok(Response::new(event_scheduler::AssignUnregisteredResponse {
+            seed: outcome.seed,
+            assigned: outcome.assigned.into_iter().map(|s| s.into()).collect(),
+            unassigned: outcome.unassigned.into_iter().map(|s| s.into()).collect(),
+            applied: outcome.applied,
+        }))
+    }
+
+    async fn create_event_impl(
+        &self,
+        request: Request<event_scheduler::EventCreateRequest>,
+    ) -> Result<Response<event_scheduler::EventCreateResponse>, EventChangeError> {
+        let caller = authenticated_user(&request).ok_or(EventChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(EventChangeError::PermissionDenied);
+        }
+
+        let event = request
+            .into_inner()
+            .event
+            .ok_or(EventChangeError::InvalidEvent(
+                "The event is missing".to_string(),
+            ))?;
+
+        let event = Mutation::create_event(&self.database, event.into(), self.sections).await?;
+
+        info!("User {} created the event {}", caller.id, event.id);
+
+        Ok(Response::new(event_scheduler::EventCreateResponse {
+            event_id: event.id,
+        }))
+    }
+
+    async fn update_event_impl(
+        &self,
+        request: Request<event_scheduler::EventUpdateRequest>,
+    ) -> Result<Response<event_scheduler::EventUpdateResponse>, EventChangeError> {
+        let caller = authenticated_user(&request).ok_or(EventChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(EventChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let event = body.event.ok_or(EventChangeError::InvalidEvent(
+            "The event is missing".to_string(),
+        ))?;
+
+        Mutation::update_event(&self.database, body.event_id, event.into(), self.sections).await?;
+
+        info!("User {} updated the event {}", caller.id, body.event_id);
+
+        Ok(Response::new(event_scheduler::EventUpdateResponse {}))
+    }
+
+    async fn delete_event_impl(
+        &self,
+        request: Request<event_scheduler::EventDeleteRequest>,
+    ) -> Result<Response<event_scheduler::EventDeleteResponse>, EventChangeError> {
+        let caller = authenticated_user(&request).ok_or(EventChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(EventChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let policy = if body.cascade {
+            EventDeletePolicy::Cascade
+        } else {
+            EventDeletePolicy::Refuse
+        };
+
+        let deleted = Mutation::delete_event(&self.database, body.event_id, policy).await?;
+
+        info!(
+            "User {} deleted the event {}, removing {} subscriptions",
+            caller.id,
+            deleted.event.id,
+            deleted.cancelled.len()
+        );
+
+        let mut rounds_by_user: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+        for subscription in &deleted.cancelled {
+            rounds_by_user
+                .entry(subscription.user_id)
+                .or_default()
+                .push(subscription.round);
+        }
+
+        for (user_id, rounds) in rounds_by_user {
+            let notification = StudentNotification {
+                notification: Some(student_notification::Notification::EventCancelled(
+                    event_scheduler::EventCancelled {
+                        event_id: deleted.event.id,
+                        name: deleted.event.name.clone(),
+                        rounds,
+                    },
+                )),
+            };
+
+            self.notify_student(user_id, notification).await;
+        }
+
+        self.notify_subscriber_counts(vec![deleted.event.id]);
+
+        Ok(Response::new(event_scheduler::EventDeleteResponse {
+            cancelled: deleted.cancelled.into_iter().map(|c| c.into()).collect(),
+        }))
+    }
+
+    async fn capacities_impl(
+        &self,
+        request: Request<event_scheduler::CapacitiesRequest>,
+    ) -> Result<Response<event_scheduler::CapacitiesResponse>, CapacityChangeError> {
+        let caller = authenticated_user(&request).ok_or(CapacityChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(CapacityChangeError::PermissionDenied);
+        }
+
+        let filter: CapacityFilter = request.into_inner().filter.unwrap_or_default().into();
+
+        let capacities = Query::get_capacities(&self.database, &filter).await?;
+
+        Ok(Response::new(event_scheduler::CapacitiesResponse {
+            capacities: capacities.into_iter().map(|c| c.into()).collect(),
+        }))
+    }
+
+    async fn set_capacities_impl(
+        &self,
+        request: Request<event_scheduler::CapacitySetRequest>,
+    ) -> Result<Response<event_scheduler::CapacitySetResponse>, CapacityChangeError> {
+        let caller = authenticated_user(&request).ok_or(CapacityChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(CapacityChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let filter: CapacityFilter = body.filter.unwrap_or_default().into();
+
+        let changes = Mutation::set_capacities(
+            &self.database,
+            &filter,
+            body.max_users,
+            CapacityOptions {
+                force: body.force,
+                dry_run: body.dry_run,
+                rules: self.subscription_rules,
+            },
+        )
+        .await?;
+
+        info!(
+            "User {} set {} capacities to {} (applied: {}), bumping {} subscriptions",
+            caller.id,
+            changes.changed.len(),
+            body.max_users,
+            changes.applied,
+            changes.bumped.len()
+        );
+
+        if changes.applied {
+            for student in &changes.bumped {
+                self.notify_student(student.user_id, (*student).into())
+                    .await;
+            }
+            self.notify_promotions(&changes.promotions).await;
+            self.notify_subscriber_counts(changes.affected_event_ids());
+        }
+
+        Ok(Response::new(event_scheduler::CapacitySetResponse {
+            changed: changes.changed.into_iter().map(|c| c.into()).collect(),
+            bumped: changes.bumped.into_iter().map(|s| s.into()).collect(),
+            applied: changes.applied,
+        }))
+    }
+
+    async fn import_events_impl(
+        &self,
+        request: Request<event_scheduler::EventImportRequest>,
+    ) -> Result<Response<event_scheduler::EventImportResponse>, ImportEventsError> {
+        let caller = authenticated_user(&request).ok_or(ImportEventsError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(ImportEventsError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let columns = body.columns.ok_or(ImportEventsError::MissingColumns)?;
+
+        let outcome = EventImport::import_xlsx(
+            &self.database,
+            body.workbook,
+            EventImportOptions {
+                sheet: body.sheet,
+                columns: columns.into(),
+                dry_run: body.dry_run,
+                rules: self.subscription_rules,
+                sections: self.sections,
+            },
+        )
+        .await?;
+
+        info!(
+            "User {} imported the events: {} created, {} updated, {} invalid cells (applied: {})",
+            caller.id,
+            outcome.created,
+            outcome.updated,
+            outcome.errors.len(),
+            outcome.applied
+        );
+
+        if outcome.applied {
+            self.notify_promotions(&outcome.promotions).await;
+            self.notify_subscriber_counts(outcome.affected_event_ids());
+        }
+
+        Ok(Response::new(event_scheduler::EventImportResponse {
+            created: outcome.created as u32,
+            updated: outcome.updated as u32,
+            errors: outcome.errors.into_iter().map(|e| e.into()).collect(),
+            applied: outcome.applied,
+        }))
+    }
+
+    async fn import_roster_impl(
+        &self,
+        request: Request<event_scheduler::RosterImportRequest>,
+    ) -> Result<Response<event_scheduler::RosterImportResponse>, ImportRosterError> {
+        let caller = authenticated_user(&request).ok_or(ImportRosterError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(ImportRosterError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+
+        let outcome = RosterImport::import_csv(
+            &self.database,
+            &body.roster,
+            RosterImportOptions {
+                deactivate_missing: body.deactivate_missing,
+                dry_run: body.dry_run,
+                rules: self.subscription_rules,
+                sections: self.sections,
+            },
+        )
+        .await?;
+
+        info!(
+            "User {} imported the roster: {} created, {} updated, {} unchanged, {} deactivated, {} invalid rows (applied: {})",
+            caller.id,
+            outcome.created,
+            outcome.updated,
+            outcome.unchanged,
+            outcome.deactivated.len(),
+            outcome.errors.len(),
+            outcome.applied
+        );
+
+        if outcome.applied {
+            self.notify_promotions(&outcome.changes.promotions).await;
+            self.notify_subscriber_counts(outcome.changes.affected_event_ids());
+        }
+
+        Ok(Response::new(event_scheduler::RosterImportResponse {
+            created: outcome.created as u32,
+            updated: outcome.updated as u32,
+            unchanged: outcome.unchanged as u32,
+            deactivated_user_ids: outcome.deactivated,
+            errors: outcome.errors.into_iter().map(|e| e.into()).collect(),
+            applied: outcome.applied,
+        }))
+    }
+
+    async fn search_users_impl(
+        &self,
+        request: Request<event_scheduler::UserSearchRequest>,
+    ) -> Result<Response<event_scheduler::UserSearchResponse>, UsersError> {
+        let caller = authenticated_user(&request).ok_or(UsersError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(UsersError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let role = body
+            .role
+            .map(|role| UserRole::try_from(role).map_err(|_| UsersError::InvalidRole(role)))
+            .transpose()?;
+
+        let page = Query::search_users(
+            &self.database,
+            &UserSearch {
+                text: body.text,
+                class: body.class,
+                section: body.section,
+                role: role.map(|role| role.into()),
+                include_inactive: body.include_inactive,
+                page: body.page,
+                page_size: body.page_size.unwrap_or(50),
+            },
+        )
+        .await?;
+
+        Ok(Response::new(event_scheduler::UserSearchResponse {
+            users: page.users.into_iter().map(|user| user.into()).collect(),
+            total: page.total,
+            pages: page.pages,
+        }))
+    }
+
+    async fn update_user_impl(
+        &self,
+        request: Request<event_scheduler::UserUpdateRequest>,
+    ) -> Result<Response<event_scheduler::UserUpdateResponse>, UserChangeError> {
+        let caller = authenticated_user(&request).ok_or(UserChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(UserChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let role = body
+            .role
+            .map(|role| {
+                UserRole::try_from(role)
+                    .map_err(|_| UserChangeError::InvalidUser(format!("Invalid role {role}")))
+            })
+            .transpose()?;
+
+        if body.user_id == caller.id && role.is_some_and(|role| role != UserRole::Admin) {
+            return Err(UserChangeError::OwnAccount);
+        }
+
+        let user = Mutation::update_user(
+            &self.database,
+            body.user_id,
+            UserUpdate {
+                section: body.section,
+                class: body.class,
+                role: role.map(|role| role.into()),
+            },
+            self.sections,
+        )
+        .await?;
+
+        info!("User {} updated the user {}", caller.id, user.id);
+
+        Ok(Response::new(event_scheduler::UserUpdateResponse {
+            user: Some(user.into()),
+        }))
+    }
+
+    async fn set_user_active_impl(
+        &self,
+        request: Request<event_scheduler::UserActiveSetRequest>,
+    ) -> Result<Response<event_scheduler::UserActiveSetResponse>, UserChangeError> {
+        let caller = authenticated_user(&request).ok_or(UserChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(UserChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        if body.user_id == caller.id && !body.active {
+            return Err(UserChangeError::OwnAccount);
+        }
+
+        let changes = Mutation::set_user_active(
+            &self.database,
+            body.user_id,
+            body.active,
+            self.subscription_rules,
+        )
+        .await?;
+
+        info!(
+            "User {} set the user {} as active: {}, removing {} subscriptions",
+            caller.id,
+            body.user_id,
+            body.active,
+            changes.removed.len()
+        );
+
+        self.notify_promotions(&changes.promotions).await;
+        self.notify_subscriber_counts(changes.affected_event_ids());
+
+        Ok(Response::new(event_scheduler::UserActiveSetResponse {
+            removed: changes.removed.into_iter().map(|s| s.into()).collect(),
+        }))
+    }
+
+    async fn delete_user_impl(
+        &self,
+        request: Request<event_scheduler::UserDeleteRequest>,
+    ) -> Result<Response<event_scheduler::UserDeleteResponse>, UserChangeError> {
+        let caller = authenticated_user(&request).ok_or(UserChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(UserChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        if body.user_id == caller.id {
+            return Err(UserChangeError::OwnAccount);
+        }
+
+        let changes =
+            Mutation::delete_user(&self.database, body.user_id, self.subscription_rules).await?;
+
+        info!(
+            "User {} deleted the user {}, removing {} subscriptions",
+            caller.id,
+            body.user_id,
+            changes.removed.len()
+        );
+
+        self.notify_promotions(&changes.promotions).await;
+        self.notify_subscriber_counts(changes.affected_event_ids());
+
+        Ok(Response::new(event_scheduler::UserDeleteResponse {
+            removed: changes.removed.into_iter().map(|s| s.into()).collect(),
+        }))
+    }
+
+    async fn editions_impl(
+        &self,
+        request: Request<event_scheduler::EditionsRequest>,
+    ) -> Result<Response<event_scheduler::EditionsResponse>, EditionsError> {
+        let caller = authenticated_user(&request).ok_or(EditionsError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(EditionsError::PermissionDenied);
+        }
+
+        let editions = Query::get_editions(&self.database).await?;
+
+        Ok(Response::new(event_scheduler::EditionsResponse {
+            editions: editions.into_iter().map(|edition| edition.into()).collect(),
+        }))
+    }
+
+    async fn create_edition_impl(
+        &self,
+        request: Request<event_scheduler::EditionCreateRequest>,
+    ) -> Result<Response<event_scheduler::EditionCreateResponse>, EditionChangeError> {
+        let caller = authenticated_user(&request).ok_or(EditionChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(EditionChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+
+        let rounds = body
+            .rounds
+            .into_iter()
+            .map(parse_new_round)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let edition = Mutation::create_edition(
+            &self.database,
+            NewEdition {
+                name: body.name,
+                rounds,
+            },
+        )
+        .await?;
+
+        info!("User {} created the edition {}", caller.id, edition.id);
+
+        Ok(Response::new(event_scheduler::EditionCreateResponse {
+            edition: Some(edition.into()),
+        }))
+    }
+
+    async fn activate_edition_impl(
+        &self,
+        request: Request<event_scheduler::EditionActivateRequest>,
+    ) -> Result<Response<event_scheduler::EditionActivateResponse>, EditionChangeError> {
+        let caller = authenticated_user(&request).ok_or(EditionChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(EditionChangeError::PermissionDenied);
+        }
+
+        let edition_id = request.into_inner().edition_id;
+
+        let edition = Mutation::activate_edition(&self.database, edition_id).await?;
+
+        info!("User {} activated the edition {}", caller.id, edition.id);
+
+        Ok(Response::new(event_scheduler::EditionActivateResponse {
+            edition: Some(edition.into()),
+        }))
+    }
+
+    async fn archive_edition_impl(
+        &self,
+        request: Request<event_scheduler::EditionArchiveRequest>,
+    ) -> Result<Response<event_scheduler::EditionArchiveResponse>, EditionChangeError> {
+        let caller = authenticated_user(&request).ok_or(EditionChangeError::Unauthenticated)?;
+        if !is_admin(&caller) {
+            return Err(EditionChangeError::PermissionDenied);
+        }
+
+        let edition_id = request.into_inner().edition_id;
+
+        let edition = Mutation::archive_edition(&self.database, edition_id).await?;
+
+        info!("User {} archived the edition {}", caller.id, edition.id);
+
+        Ok(Response::new(event_scheduler::EditionArchiveResponse {
+            edition: Some(edition.into()),
+        }))
+    }
+
+    async fn registration_window_impl(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowRequest>,
+    ) -> Result<Response<event_scheduler::RegistrationWindowResponse>, RegistrationWindowError>
+    {
+        let caller =
+            authenticated_user(&request).ok_or(RegistrationWindowError::Unauthenticated)?;
+
+        let window = Query::registration_window_for_user(&self.database, &caller).await?;
+        let open = is_registration_open(window.as_ref());
+
+        Ok(Response::new(event_scheduler::RegistrationWindowResponse {
+            opens_at: window
+                .as_ref()
+                .map(|w| convert_naive_date_time_to_timestamp(w.opens_at)),
+            closes_at: window
+                .as_ref()
+                .map(|w| convert_naive_date_time_to_timestamp(w.closes_at)),
+            open,
+            mode: self.registration_mode.into(),
+        }))
+    }
+
+    async fn registration_windows_impl(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowsRequest>,
+    ) -> Result<Response<event_scheduler::RegistrationWindowsResponse>, RegistrationWindowsError>
+    {
+        let caller =
+            authenticated_user(&request).ok_or(RegistrationWindowsError::Unauthenticated)?;
+        if !is_staff(&caller) {
+            return Err(RegistrationWindowsError::PermissionDenied);
+        }
+
+        let edition_id = request.into_inner().edition_id;
+
+        let windows = Query::get_registration_windows(&self.database, edition_id).await?;
+
+        Ok(Response::new(
+            event_scheduler::RegistrationWindowsResponse {
+                windows: windows.into_iter().map(|window| window.into()).collect(),
+            },
+        ))
+    }
+
+    async fn create_registration_window_impl(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowCreateRequest>,
+    ) -> Result<
+        Response<event_scheduler::RegistrationWindowCreateResponse>,
+        RegistrationWindowChangeError,
+    > {
+        let caller =
+            authenticated_user(&request).ok_or(RegistrationWindowChangeError::Unauthenticated)?;
+        if !is_staff(&caller) {
+            return Err(RegistrationWindowChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let window = parse_registration_window(body.window)?;
+
+        let window = Mutation::create_registration_window(
+            &self.database,
+            body.edition_id,
+            window,
+            self.sections,
+        )
+        .await?;
+
+        info!(
+            "User {} created the registration window {} of the edition {}",
+            caller.id, window.id, window.edition_id
+        );
+
+        Ok(Response::new(
+            event_scheduler::RegistrationWindowCreateResponse {
+                window_id: window.id,
+            },
+        ))
+    }
+
+    async fn update_registration_window_impl(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowUpdateRequest>,
+    ) -> Result<
+        Response<event_scheduler::RegistrationWindowUpdateResponse>,
+        RegistrationWindowChangeError,
+    > {
+        let caller =
+            authenticated_user(&request).ok_or(RegistrationWindowChangeError::Unauthenticated)?;
+        if !is_staff(&caller) {
+            return Err(RegistrationWindowChangeError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+        let window = parse_registration_window(body.window)?;
+
+        Mutation::update_registration_window(&self.database, body.window_id, window, self.sections)
+            .await?;
+
+        info!(
+            "User {} updated the registration window {}",
+            caller.id, body.window_id
+        );
+
+        Ok(Response::new(
+            event_scheduler::RegistrationWindowUpdateResponse {},
+        ))
+    }
+
+    async fn delete_registration_window_impl(
+        &self,
+        request: Request<event_scheduler::RegistrationWindowDeleteRequest>,
+    ) -> Result<
+        Response<event_scheduler::RegistrationWindowDeleteResponse>,
+        RegistrationWindowChangeError,
+    > {
+        let caller =
+            authenticated_user(&request).ok_or(RegistrationWindowChangeError::Unauthenticated)?;
+        if !is_staff(&caller) {
+            return Err(RegistrationWindowChangeError::PermissionDenied);
+        }
+
+        let window_id = request.into_inner().window_id;
+
+        Mutation::delete_registration_window(&self.database, window_id).await?;
+
+        info!(
+            "User {} deleted the registration window {window_id}",
+            caller.id
+        );
+
+        Ok(Response::new(
+            event_scheduler::RegistrationWindowDeleteResponse {},
+        ))
+    }
+
+    async fn join_event_impl(
+        &self,
+        request: Request<event_scheduler::EventJoinRequest>,
+    ) -> Result<Response<event_scheduler::EventJoinResponse>, EventJoinResponseError> {
+        let caller = authenticated_user(&request).ok_or(EventJoinResponseError::Unauthenticated)?;
+        if !is_staff(&caller) {
+            return Err(EventJoinResponseError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+
+        // Staff are never subscribed themselves, they always act on a student
+        let user_id = body
+            .on_behalf_of_user_id
+            .ok_or(EventJoinResponseError::MissingUser)?;
+
+        Mutation::join_event(
+            &self.database,
+            user_id,
+            EventSubscription {
+                round: body.round,
+                event_id: body.event_id,
+            },
+        )
+        .await?
+        .ok_or(EventJoinResponseError::NotSubscribed(
+            user_id,
+            body.event_id,
+            body.round,
+        ))?;
+
+        Ok(Response::new(event_scheduler::EventJoinResponse {}))
+    }
+
+    async fn leave_event_impl(
+        &self,
+        request: Request<event_scheduler::EventLeaveRequest>,
+    ) -> Result<Response<event_scheduler::EventLeaveResponse>, EventLeaveResponseError> {
+        let caller =
+            authenticated_user(&request).ok_or(EventLeaveResponseError::Unauthenticated)?;
+        if !is_staff(&caller) {
+            return Err(EventLeaveResponseError::PermissionDenied);
+        }
+
+        let body = request.into_inner();
+
+        // Staff are never subscribed themselves, they always act on a student
+        let user_id = body
+            .on_behalf_of_user_id
+            .ok_or(EventLeaveResponseError::MissingUser)?;
+
+        Mutation::leave_event(
+            &self.database,
+            user_id,
+            EventSubscription {
+                round: body.round,
+                event_id: body.event_id,
+            },
+        )
+        .await?
+        .ok_or(EventLeaveResponseError::NotSubscribed(
+            user_id,
+            body.event_id,
+            body.round,
+        ))?;
+
+        Ok(Response::new(event_scheduler::EventLeaveResponse {}))
     }
 
     async fn event_users_status_impl(
         &self,
         request: Request<EventUsersStatusRequest>,
     ) -> Result<Response<ResponseStreamEventUsersStatus>, ResponseStreamEventUsersStatusError> {
+        let caller = authenticated_user(&request)
+            .ok_or(ResponseStreamEventUsersStatusError::Unauthenticated)?;
+        if !is_staff(&caller) {
+            return Err(ResponseStreamEventUsersStatusError::PermissionDenied);
+        }
+
         let body = request.into_inner();
 
         let event_users =
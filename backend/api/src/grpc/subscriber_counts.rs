@@ -0,0 +1,174 @@
+use crate::grpc::event_scheduler::{RoundSubscriberCount, SubscriberCountStreamUpdate};
+use futures::stream;
+use std::collections::HashMap;
+use svelte_rust_event_scheduler_service::sea_orm::{DatabaseConnection, DbErr};
+use svelte_rust_event_scheduler_service::{EventRoundSeats, Query};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tonic::codegen::tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+use tracing::error;
+
+/// The updates kept for a stream that doesn't keep up, it is closed once it misses more of them
+const UPDATES_BUFFER: usize = 256;
+
+type Subscription = (
+    Vec<SubscriberCountStreamUpdate>,
+    broadcast::Receiver<SubscriberCountStreamUpdate>,
+);
+
+enum Command {
+    /// Send the current count of the given events to every stream
+    Notify(Vec<i32>),
+    /// Start a stream, with a snapshot of every event and the updates following it
+    Subscribe(oneshot::Sender<Result<Subscription, DbErr>>),
+}
+
+/// The subscriber count streams. The counts are read and sent by a single task, in the order the
+/// changes were notified, so that a stream never gets a count older than the one it has.
+#[derive(Clone)]
+pub(super) struct SubscriberCounts {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl SubscriberCounts {
+    pub(super) fn spawn(database: DatabaseConnection) -> Self {
+        let (commands, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(run(database, receiver));
+
+        Self { commands }
+    }
+
+    /// Sends the current subscriber count of the given events to every stream, without waiting
+    /// for it
+    pub(super) fn notify(&self, event_ids: Vec<i32>) {
+        if event_ids.is_empty() {
+            return;
+        }
+
+        // The task only stops once every handle is dropped
+        let _ = self.commands.send(Command::Notify(event_ids));
+    }
+
+    /// Starts a stream with the count of every event of the active edition, followed by the
+    /// updates. A stream that falls behind ends with an error, the client then reconnects.
+    pub(super) async fn subscribe(
+        &self,
+    ) -> Result<impl Stream<Item = Result<SubscriberCountStreamUpdate, Status>>, DbErr> {
+        let (reply, response) = oneshot::channel();
+
+        self.commands
+            .send(Command::Subscribe(reply))
+            .map_err(|_| DbErr::Custom("The subscriber counts are not running".to_string()))?;
+
+        let (snapshot, receiver) = response
+            .await
+            .map_err(|_| DbErr::Custom("The subscriber counts are not running".to_string()))??;
+
+        let updates = stream::unfold(Some(receiver), |receiver| async move {
+            let mut receiver = receiver?;
+
+            match receiver.recv().await {
+                Ok(update) => Some((Ok(update), Some(receiver))),
+                Err(RecvError::Lagged(missed)) => Some((
+                    Err(Status::aborted(format!(
+                        "The stream missed {missed} updates, reconnect to get the counts again"
+                    ))),
+                    None,
+                )),
+                Err(RecvError::Closed) => None,
+            }
+        });
+
+        Ok(tonic::codegen::tokio_stream::iter(snapshot.into_iter().map(Ok)).chain(updates))
+    }
+}
+
+async fn run(database: DatabaseConnection, mut commands: mpsc::UnboundedReceiver<Command>) {
+    let (updates, _) = broadcast::channel(UPDATES_BUFFER);
+    let mut sequence = 0;
+
+    while let Some(command) = commands.recv().await {
+        match command {
+            Command::Notify(event_ids) => {
+                let seats = match Query::get_events_seats_by_ids(&database, &event_ids).await {
+                    Ok(seats) => seats,
+                    Err(err) => {
+                        error!("Failed to get the subscriber counts of {event_ids:?}: {err}");
+                        continue;
+                    }
+                };
+
+                for mut update in updates_from_seats(&event_ids, seats) {
+                    sequence += 1;
+                    update.sequence = sequence;
+
+                    // Fails when no stream is open, the update is then dropped
+                    let _ = updates.send(update);
+                }
+            }
+            Command::Subscribe(reply) => {
+                let snapshot = snapshot(&database).await.map(|snapshot| {
+                    let snapshot = snapshot
+                        .into_iter()
+                        .map(|update| SubscriberCountStreamUpdate {
+                            sequence,
+                            snapshot: true,
+                            ..update
+                        })
+                        .collect();
+
+                    (snapshot, updates.subscribe())
+                });
+
+                let _ = reply.send(snapshot);
+            }
+        }
+    }
+}
+
+async fn snapshot(
+    database: &DatabaseConnection,
+) -> Result<Vec<SubscriberCountStreamUpdate>, DbErr> {
+    let event_ids = Query::get_all_events(database)
+        .await?
+        .into_iter()
+        .map(|event| event.id)
+        .collect::<Vec<_>>();
+    let seats = Query::get_events_seats(database).await?;
+
+    Ok(updates_from_seats(&event_ids, seats))
+}
+
+/// Builds an update for every given event from its seats, the events without seats have no
+/// subscribers
+fn updates_from_seats(
+    event_ids: &[i32],
+    seats: Vec<EventRoundSeats>,
+) -> Vec<SubscriberCountStreamUpdate> {
+    let mut rounds_by_event: HashMap<i32, Vec<RoundSubscriberCount>> = HashMap::new();
+    for seats in seats {
+        rounds_by_event
+            .entry(seats.event_id)
+            .or_default()
+            .push(RoundSubscriberCount {
+                round: seats.round,
+                subscriber_count: seats.registered,
+            });
+    }
+
+    event_ids
+        .iter()
+        .map(|&event_id| {
+            let rounds = rounds_by_event.remove(&event_id).unwrap_or_default();
+
+            SubscriberCountStreamUpdate {
+                id: event_id,
+                subscriber_count: rounds.iter().map(|r| r.subscriber_count).sum(),
+                rounds,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
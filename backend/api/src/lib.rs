@@ -1,7 +1,6 @@
 use grpc::start_server;
 
-// TODO: Complete auth and enable its module
-// mod auth;
+mod auth;
 mod grpc;
 
 #[tokio::main]
@@ -12,22 +12,54 @@ pub struct Model {
     pub zone: String,
     pub floor: String,
     pub minimum_section: i32,
+    pub round_span: i32,
+    pub edition_id: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::edition::Entity",
+        from = "Column::EditionId",
+        to = "super::edition::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Restrict"
+    )]
+    Edition,
+    #[sea_orm(has_many = "super::event_preference::Entity")]
+    EventPreference,
     #[sea_orm(has_many = "super::event_user::Entity")]
     EventUser,
+    #[sea_orm(has_many = "super::event_waitlist::Entity")]
+    EventWaitlist,
     #[sea_orm(has_many = "super::round_max_users::Entity")]
     RoundMaxUsers,
 }
 
+impl Related<super::edition::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Edition.def()
+    }
+}
+
+impl Related<super::event_preference::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EventPreference.def()
+    }
+}
+
 impl Related<super::event_user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::EventUser.def()
     }
 }
 
+impl Related<super::event_waitlist::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EventWaitlist.def()
+    }
+}
+
 impl Related<super::round_max_users::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::RoundMaxUsers.def()
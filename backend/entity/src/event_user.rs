@@ -32,6 +32,14 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     User,
+    #[sea_orm(
+        belongs_to = "super::round::Entity",
+        from = "Column::Round",
+        to = "super::round::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Restrict"
+    )]
+    Round,
 }
 
 impl Related<super::event::Entity> for Entity {
@@ -46,4 +54,10 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::round::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Round.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -2,7 +2,13 @@
 
 pub mod prelude;
 
+pub mod edition;
 pub mod event;
+pub mod event_preference;
 pub mod event_user;
+pub mod event_waitlist;
+pub mod registration_window;
+pub mod round;
 pub mod round_max_users;
+pub mod sea_orm_active_enums;
 pub mod user;
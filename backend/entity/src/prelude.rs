@@ -1,6 +1,11 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+pub use super::edition::Entity as Edition;
 pub use super::event::Entity as Event;
+pub use super::event_preference::Entity as EventPreference;
 pub use super::event_user::Entity as EventUser;
+pub use super::event_waitlist::Entity as EventWaitlist;
+pub use super::registration_window::Entity as RegistrationWindow;
+pub use super::round::Entity as Round;
 pub use super::round_max_users::Entity as RoundMaxUsers;
 pub use super::user::Entity as User;
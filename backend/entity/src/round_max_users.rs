@@ -22,6 +22,14 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Event,
+    #[sea_orm(
+        belongs_to = "super::round::Entity",
+        from = "Column::Round",
+        to = "super::round::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Round,
 }
 
 impl Related<super::event::Entity> for Entity {
@@ -30,4 +38,10 @@ impl Related<super::event::Entity> for Entity {
     }
 }
 
+impl Related<super::round::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Round.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
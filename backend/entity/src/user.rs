@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+use super::sea_orm_active_enums::UserRole;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -13,13 +14,24 @@ pub struct Model {
     pub interactive_done: bool,
     pub section: i32,
     pub class: Option<String>,
-    pub admin: bool,
+    pub role: UserRole,
+    pub active: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::event_preference::Entity")]
+    EventPreference,
     #[sea_orm(has_many = "super::event_user::Entity")]
     EventUser,
+    #[sea_orm(has_many = "super::event_waitlist::Entity")]
+    EventWaitlist,
+}
+
+impl Related<super::event_preference::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EventPreference.def()
+    }
 }
 
 impl Related<super::event_user::Entity> for Entity {
@@ -28,6 +40,12 @@ impl Related<super::event_user::Entity> for Entity {
     }
 }
 
+impl Related<super::event_waitlist::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EventWaitlist.def()
+    }
+}
+
 impl Related<super::event::Entity> for Entity {
     fn to() -> RelationDef {
         super::event_user::Relation::Event.def()
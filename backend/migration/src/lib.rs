@@ -1,11 +1,36 @@
 pub use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
 
 mod m20220101_000001_create_table;
 mod m20240212_080458_import_xlsx_file;
 mod m20240214_000121_import_csv;
+mod m20240216_000000_add_user_role;
+mod m20240217_000000_create_registration_window;
+mod m20240218_000000_create_event_waitlist;
+mod m20240219_000000_create_event_preference;
+mod m20240220_000000_add_event_double_length;
+mod m20240221_000000_add_event_round_span;
+mod m20240222_000000_create_round;
+mod m20240223_000000_create_edition;
+mod m20240224_000000_add_user_active;
 
 pub struct Migrator;
 
+/// Whether the table has any row, the data migrations only apply to the databases that were
+/// seeded before the data was split out of the migrations
+pub(crate) async fn has_rows(manager: &SchemaManager<'_>, table: &str) -> Result<bool, DbErr> {
+    let db = manager.get_connection();
+
+    let row = db
+        .query_one(Statement::from_string(
+            db.get_database_backend(),
+            format!("SELECT 1 FROM \"{table}\" LIMIT 1"),
+        ))
+        .await?;
+
+    Ok(row.is_some())
+}
+
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
@@ -13,6 +38,15 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20240212_080458_import_xlsx_file::Migration),
             Box::new(m20240214_000121_import_csv::Migration),
+            Box::new(m20240216_000000_add_user_role::Migration),
+            Box::new(m20240217_000000_create_registration_window::Migration),
+            Box::new(m20240218_000000_create_event_waitlist::Migration),
+            Box::new(m20240219_000000_create_event_preference::Migration),
+            Box::new(m20240220_000000_add_event_double_length::Migration),
+            Box::new(m20240221_000000_add_event_round_span::Migration),
+            Box::new(m20240222_000000_create_round::Migration),
+            Box::new(m20240223_000000_create_edition::Migration),
+            Box::new(m20240224_000000_add_user_active::Migration),
         ]
     }
 }
@@ -1,74 +1,17 @@
-use crate::sea_orm::TransactionTrait;
-use entity::user;
 use sea_orm_migration::prelude::*;
-use sea_orm_migration::sea_orm::entity::*;
-use serde::Deserialize;
 
+/// Used to import the students of the 2024 forum from a CSV file, they are now imported by
+/// the `seed` command. Kept as a no-op so that the databases where it ran stay consistent.
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
-#[derive(Deserialize)]
-pub struct User {
-    pub name: String,
-    pub email: String,
-    pub section: i32,
-}
-
-mod csv_deserialization {
-    use csv::ReaderBuilder;
-
-    use super::User;
-
-    pub(crate) fn parse_csv() -> Result<Vec<User>, csv::Error> {
-        let path = format!(
-            "{}/../seed/csv/ATTIVITÀ FORUM DEFINITIVE.csv",
-            env!("CARGO_MANIFEST_DIR")
-        );
-        let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
-
-        let mut users: Vec<User> = Vec::new();
-
-        for result in reader.deserialize() {
-            let user: User = result?;
-            users.push(user);
-        }
-
-        Ok(users)
-    }
-}
-
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
-    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        let db = manager.get_connection();
-
-        let users = csv_deserialization::parse_csv()
-            .unwrap()
-            .iter()
-            .map(|user| user::ActiveModel {
-                name: Set(Some(user.name.clone())),
-                email: Set(user.email.clone()),
-                section: Set(user.section),
-                ..Default::default()
-            })
-            .collect::<Vec<_>>();
-
-        let transaction = db.begin().await?;
-
-        user::Entity::insert_many(users).exec(&transaction).await?;
-
-        transaction.commit().await?;
-
+    async fn up(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
         Ok(())
     }
-    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        let db = manager.get_connection();
-        let transaction = db.begin().await?;
-
-        user::Entity::delete_many().exec(&transaction).await?;
-
-        transaction.commit().await?;
 
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
         Ok(())
     }
 }
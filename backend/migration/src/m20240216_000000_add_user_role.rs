@@ -0,0 +1,113 @@
+use sea_orm_migration::prelude::extension::postgres::Type;
+use sea_orm_migration::prelude::*;
+
+/// Replaces the `admin` flag of the users with a role (student, teacher or admin),
+/// the users that were admins become admins, everyone else becomes a student
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(UserRole::Enum)
+                    .values([UserRole::Student, UserRole::Teacher, UserRole::Admin])
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(
+                        ColumnDef::new(User::Role)
+                            .custom(UserRole::Enum)
+                            .not_null()
+                            .default(Expr::val("student").as_enum(UserRole::Enum)),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(User::Table)
+                    .value(User::Role, Expr::val("admin").as_enum(UserRole::Enum))
+                    .and_where(Expr::col(User::Admin).eq(true))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::Admin)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(
+                        ColumnDef::new(User::Admin)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(User::Table)
+                    .value(User::Admin, true)
+                    .and_where(Expr::col(User::Role).eq(Expr::val("admin").as_enum(UserRole::Enum)))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::Role)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(UserRole::Enum).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Admin,
+    Role,
+}
+
+#[derive(DeriveIden)]
+enum UserRole {
+    #[sea_orm(iden = "user_role")]
+    Enum,
+    Student,
+    Teacher,
+    Admin,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+/// Registration windows, during which students can change their subscriptions.
+///
+/// A window applies to everyone, to a section or to a class (the most specific one wins),
+/// when no window applies the registrations are always open
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RegistrationWindow::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RegistrationWindow::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RegistrationWindow::Section).integer())
+                    .col(ColumnDef::new(RegistrationWindow::Class).string())
+                    .col(
+                        ColumnDef::new(RegistrationWindow::OpensAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RegistrationWindow::ClosesAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RegistrationWindow::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RegistrationWindow {
+    Table,
+    Id,
+    Section,
+    Class,
+    OpensAt,
+    ClosesAt,
+}
@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+/// Per-round waitlists of the full events, a student can wait for one event in every round
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EventWaitlist::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(EventWaitlist::UserId).integer().not_null())
+                    .col(ColumnDef::new(EventWaitlist::Round).integer().not_null())
+                    .col(ColumnDef::new(EventWaitlist::EventId).integer().not_null())
+                    .col(
+                        ColumnDef::new(EventWaitlist::CreatedAt)
+                            .date_time()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_event_waitlist_event_id")
+                            .from_tbl(EventWaitlist::Table)
+                            .from_col(EventWaitlist::EventId)
+                            .to_tbl(Event::Table)
+                            .to_col(Event::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_event_waitlist_user_id")
+                            .from_tbl(EventWaitlist::Table)
+                            .from_col(EventWaitlist::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk_event_waitlist")
+                            .col(EventWaitlist::UserId)
+                            .col(EventWaitlist::Round),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_event_waitlist_event_id_round")
+                    .table(EventWaitlist::Table)
+                    .col(EventWaitlist::EventId)
+                    .col(EventWaitlist::Round)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EventWaitlist::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum EventWaitlist {
+    Table,
+    UserId,
+    Round,
+    EventId,
+    CreatedAt,
+}
@@ -0,0 +1,98 @@
+use sea_orm_migration::prelude::*;
+
+/// The ranked event preferences of the students for every round, used when registrations
+/// are allocated in batch instead of first come first served
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EventPreference::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(EventPreference::UserId).integer().not_null())
+                    .col(ColumnDef::new(EventPreference::Round).integer().not_null())
+                    .col(ColumnDef::new(EventPreference::Rank).integer().not_null())
+                    .col(
+                        ColumnDef::new(EventPreference::EventId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_event_preference_event_id")
+                            .from_tbl(EventPreference::Table)
+                            .from_col(EventPreference::EventId)
+                            .to_tbl(Event::Table)
+                            .to_col(Event::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_event_preference_user_id")
+                            .from_tbl(EventPreference::Table)
+                            .from_col(EventPreference::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk_event_preference")
+                            .col(EventPreference::UserId)
+                            .col(EventPreference::Round)
+                            .col(EventPreference::Rank),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // An event can be ranked only once in a round
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_event_preference_user_id_round_event_id")
+                    .table(EventPreference::Table)
+                    .col(EventPreference::UserId)
+                    .col(EventPreference::Round)
+                    .col(EventPreference::EventId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EventPreference::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum EventPreference {
+    Table,
+    UserId,
+    Round,
+    Rank,
+    EventId,
+}
@@ -0,0 +1,91 @@
+use sea_orm_migration::prelude::*;
+
+/// Replaces the double-length flag of the events with the number of consecutive rounds they
+/// span, the double-length events span two rounds
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Event::Table)
+                    .add_column(
+                        ColumnDef::new(Event::RoundSpan)
+                            .integer()
+                            .not_null()
+                            .default(1)
+                            .check(Expr::col(Event::RoundSpan).gte(1)),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Event::Table)
+                    .value(Event::RoundSpan, 2)
+                    .and_where(Expr::col(Event::DoubleLength).eq(true))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Event::Table)
+                    .drop_column(Event::DoubleLength)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Event::Table)
+                    .add_column(
+                        ColumnDef::new(Event::DoubleLength)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Event::Table)
+                    .value(Event::DoubleLength, true)
+                    .and_where(Expr::col(Event::RoundSpan).gte(2))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Event::Table)
+                    .drop_column(Event::RoundSpan)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    DoubleLength,
+    RoundSpan,
+}
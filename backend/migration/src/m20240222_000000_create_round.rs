@@ -0,0 +1,146 @@
+use sea_orm_migration::prelude::*;
+
+/// The rounds of the forum, with their day and time slot. In the databases that have
+/// registrations, the rounds that were implicit are created with the same ids, as placeholders
+/// whose day, time slot and label are then set by the `seed` binary of the service crate, the
+/// rows of any other round are deleted. Every `round` column now references them.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// The rounds that were implicit, two days of two rounds: the id, the day from today, and the
+/// start and end times
+const PLACEHOLDER_ROUNDS: [(i32, i32, &str, &str); 4] = [
+    (1, 0, "08:30", "10:30"),
+    (2, 0, "10:30", "12:30"),
+    (3, 1, "08:30", "10:30"),
+    (4, 1, "10:30", "12:30"),
+];
+
+/// The tables with a `round` column, and what happens to their rows when the round is deleted.
+/// The attendance is kept, a round with subscriptions can't be deleted.
+const ROUND_TABLES: [(&str, ForeignKeyAction); 4] = [
+    ("event_user", ForeignKeyAction::Restrict),
+    ("round_max_users", ForeignKeyAction::Cascade),
+    ("event_waitlist", ForeignKeyAction::Cascade),
+    ("event_preference", ForeignKeyAction::Cascade),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Round::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Round::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Round::Day).date().not_null())
+                    .col(ColumnDef::new(Round::StartTime).time().not_null())
+                    .col(ColumnDef::new(Round::EndTime).time().not_null())
+                    .col(ColumnDef::new(Round::Label).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        let mut has_registrations = false;
+        for (table, _) in ROUND_TABLES {
+            has_registrations |= crate::has_rows(manager, table).await?;
+        }
+
+        if has_registrations {
+            let mut insert = Query::insert()
+                .into_table(Round::Table)
+                .columns([
+                    Round::Id,
+                    Round::Day,
+                    Round::StartTime,
+                    Round::EndTime,
+                    Round::Label,
+                ])
+                .to_owned();
+
+            for (id, day, start_time, end_time) in PLACEHOLDER_ROUNDS {
+                insert.values_panic([
+                    id.into(),
+                    Expr::current_date().add(day),
+                    Expr::val(start_time).cast_as(Alias::new("time")),
+                    Expr::val(end_time).cast_as(Alias::new("time")),
+                    format!("Round {id}").into(),
+                ]);
+            }
+
+            manager.exec_stmt(insert).await?;
+
+            // Only the implicit rounds were valid, the other rows would break the foreign keys
+            let ids = PLACEHOLDER_ROUNDS.map(|(id, ..)| id);
+            for (table, _) in ROUND_TABLES {
+                manager
+                    .exec_stmt(
+                        Query::delete()
+                            .from_table(Alias::new(table))
+                            .and_where(Expr::col(Alias::new("round")).is_not_in(ids))
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+
+            // The ids were set explicitly, the next round gets the following one
+            manager
+                .get_connection()
+                .execute_unprepared(
+                    "SELECT setval(pg_get_serial_sequence('round', 'id'), (SELECT MAX(id) FROM round))",
+                )
+                .await?;
+        }
+
+        for (table, on_delete) in ROUND_TABLES {
+            manager
+                .create_foreign_key(
+                    ForeignKey::create()
+                        .name(format!("fk_{table}_round"))
+                        .from(Alias::new(table), Alias::new("round"))
+                        .to(Round::Table, Round::Id)
+                        .on_delete(on_delete)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (table, _) in ROUND_TABLES {
+            manager
+                .drop_foreign_key(
+                    ForeignKey::drop()
+                        .name(format!("fk_{table}_round"))
+                        .table(Alias::new(table))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .drop_table(Table::drop().table(Round::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Round {
+    Table,
+    Id,
+    Day,
+    StartTime,
+    EndTime,
+    Label,
+}
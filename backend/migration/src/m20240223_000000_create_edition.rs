@@ -0,0 +1,140 @@
+use sea_orm_migration::prelude::*;
+
+/// Editions of the forum, owning their events, rounds and registration windows (and through
+/// them the registrations). At most one edition is active, the existing data (if any) becomes
+/// the first edition, which is active.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const FIRST_EDITION_NAME: &str = "Forum 2024";
+
+/// The tables owned by an edition
+const EDITION_TABLES: [&str; 3] = ["event", "round", "registration_window"];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Edition::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Edition::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Edition::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(Edition::Active)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(ColumnDef::new(Edition::ArchivedAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Only one edition can be active
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE UNIQUE INDEX idx_edition_active ON edition (active) WHERE active",
+            )
+            .await?;
+
+        let mut has_data = false;
+        for table in EDITION_TABLES {
+            has_data |= crate::has_rows(manager, table).await?;
+        }
+
+        if has_data {
+            manager
+                .exec_stmt(
+                    Query::insert()
+                        .into_table(Edition::Table)
+                        .columns([Edition::Id, Edition::Name, Edition::Active])
+                        .values_panic([1.into(), FIRST_EDITION_NAME.into(), true.into()])
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .get_connection()
+                .execute_unprepared(
+                    "SELECT setval(pg_get_serial_sequence('edition', 'id'), (SELECT MAX(id) FROM edition))",
+                )
+                .await?;
+        }
+
+        for table in EDITION_TABLES {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Alias::new(table))
+                        .add_column(
+                            ColumnDef::new(Alias::new("edition_id"))
+                                .integer()
+                                .not_null()
+                                .default(1),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // The existing rows belong to the first edition, the edition of the new ones must be
+            // chosen explicitly
+            manager
+                .get_connection()
+                .execute_unprepared(&format!(
+                    "ALTER TABLE \"{table}\" ALTER COLUMN edition_id DROP DEFAULT"
+                ))
+                .await?;
+
+            // Archived editions keep their data, an edition with data can't be deleted
+            manager
+                .create_foreign_key(
+                    ForeignKey::create()
+                        .name(format!("fk_{table}_edition_id"))
+                        .from(Alias::new(table), Alias::new("edition_id"))
+                        .to(Edition::Table, Edition::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in EDITION_TABLES {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Alias::new(table))
+                        .drop_column(Alias::new("edition_id"))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .drop_table(Table::drop().table(Edition::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Edition {
+    Table,
+    Id,
+    Name,
+    Active,
+    ArchivedAt,
+}
@@ -0,0 +1,891 @@
+use crate::edition::in_active_edition;
+use crate::{Rounds, SubscriptionRules};
+use ::entity::{
+    event, event_preference, event_user, round_max_users, sea_orm_active_enums::UserRole, user,
+};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use sea_orm::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// A seat given to a student by the allocation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocatedSeat {
+    pub user_id: i32,
+    pub round: i32,
+    pub event_id: i32,
+    /// The rank the student gave to the event, 1 is the most wanted
+    pub rank: i32,
+}
+
+/// A round for which none of the events ranked by the student had a seat left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnallocatedSeat {
+    pub user_id: i32,
+    pub round: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct AllocationOutcome {
+    /// The seed of the random draw, running the allocation again with it on the same
+    /// preferences gives the same seats
+    pub seed: u64,
+    pub allocated: Vec<AllocatedSeat>,
+    pub unallocated: Vec<UnallocatedSeat>,
+}
+
+/// A seat given to a student that never registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssignedSeat {
+    pub user_id: i32,
+    pub round: i32,
+    pub event_id: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct AssignmentOptions {
+    /// Only report the assignments, without saving them
+    pub dry_run: bool,
+    /// Place the students of the same class in the same event when possible
+    pub keep_classmates_together: bool,
+    /// Random when not set
+    pub seed: Option<u64>,
+    pub rules: SubscriptionRules,
+}
+
+#[derive(Debug, Default)]
+pub struct AssignmentOutcome {
+    pub seed: u64,
+    pub assigned: Vec<AssignedSeat>,
+    /// The rounds for which no eligible event had a seat left
+    pub unassigned: Vec<UnallocatedSeat>,
+    /// Whether the assignments were saved
+    pub applied: bool,
+}
+
+/// The events ranked by every student, by round and by student, the most wanted first
+type RankedRounds = BTreeMap<i32, BTreeMap<i32, Vec<event_preference::Model>>>;
+
+/// The event of every student in the rounds it has one, by student and by round
+type HeldSeats = HashMap<i32, BTreeMap<i32, i32>>;
+
+pub struct Allocation;
+
+impl Allocation {
+    /// Assign the seats from the ranked preferences of the students, with a randomized serial
+    /// dictatorship: in every round the students are drawn in a random order, and each one gets
+    /// the best ranked event it is still eligible for that has seats left.
+    ///
+    /// A multi-round event also takes the seats of the student in the following rounds it spans,
+    /// when it ranked them too and it wasn't given a seat in them yet. Unless the rules allow it,
+    /// an event given to a student in a round is skipped in the other rounds.
+    ///
+    /// The subscriptions of the ranked rounds are replaced, along with the whole of the
+    /// multi-round events they belong to. The ones of the students that didn't rank a round are
+    /// kept and count against the capacity, and against the events the student can be given.
+    /// Everything happens in one transaction, with every `round_max_users` row locked.
+    pub async fn allocate_preferences(
+        db: &DbConn,
+        seed: Option<u64>,
+        rules: SubscriptionRules,
+    ) -> Result<AllocationOutcome, DbErr> {
+        let seed = seed.unwrap_or_else(rand::random);
+
+        let transaction = db.begin().await?;
+
+        let rounds = Rounds::load(&transaction).await?;
+
+        let max_users = round_max_users::Entity::find()
+            .filter(round_max_users::Column::Round.is_in(rounds.ids()))
+            .order_by_asc(round_max_users::Column::EventId)
+            .order_by_asc(round_max_users::Column::Round)
+            .lock_exclusive()
+            .all(&transaction)
+            .await?;
+
+        let preferences = event_preference::Entity::find()
+            .filter(event_preference::Column::Round.is_in(rounds.ids()))
+            .order_by_asc(event_preference::Column::Rank)
+            .all(&transaction)
+            .await?;
+
+        let ranked_rounds = Self::ranked_rounds(preferences);
+
+        let user_ids = ranked_rounds
+            .values()
+            .flat_map(|students| students.keys().copied())
+            .collect::<Vec<_>>();
+
+        let sections = user::Entity::find()
+            .filter(user::Column::Id.is_in(user_ids))
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user.section))
+            .collect::<HashMap<_, _>>();
+
+        let events = event::Entity::find()
+            .filter(in_active_edition(event::Column::EditionId))
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|event| (event.id, event))
+            .collect::<HashMap<_, _>>();
+
+        let held = event_user::Entity::find()
+            .filter(event_user::Column::Round.is_in(rounds.ids()))
+            .filter(event_user::Column::UserId.is_in(sections.keys().copied()))
+            .lock_exclusive()
+            .all(&transaction)
+            .await?;
+
+        let (released, kept) = Self::release_ranked_rounds(&ranked_rounds, &events, &rounds, held);
+
+        if !released.is_empty() {
+            let condition =
+                released
+                    .iter()
+                    .fold(Condition::any(), |condition, (user_id, rounds)| {
+                        condition.add(
+                            event_user::Column::UserId
+                                .eq(*user_id)
+                                .and(event_user::Column::Round.is_in(rounds.keys().copied())),
+                        )
+                    });
+
+            event_user::Entity::delete_many()
+                .filter(condition)
+                .exec(&transaction)
+                .await?;
+        }
+
+        let seats_left = Self::seats_left(&transaction, &max_users).await?;
+
+        let outcome = Self::draw_seats(
+            &ranked_rounds,
+            &sections,
+            &events,
+            &rounds,
+            kept,
+            seats_left,
+            rules,
+            seed,
+        );
+
+        if !outcome.allocated.is_empty() {
+            let rows = outcome
+                .allocated
+                .iter()
+                .map(|seat| event_user::ActiveModel {
+                    user_id: Set(seat.user_id),
+                    joined_at: Default::default(),
+                    left_at: Default::default(),
+                    event_id: Set(seat.event_id),
+                    round: Set(seat.round),
+                })
+                .collect::<Vec<_>>();
+
+            event_user::Entity::insert_many(rows)
+                .exec(&transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(outcome)
+    }
+
+    /// Give a seat in every round to the students without any subscription, in a random order.
+    ///
+    /// Every student gets the event it is eligible for with the most seats left, preferring the
+    /// events it doesn't have yet in another round (the only ones allowed, unless the rules allow
+    /// repeated events). When keeping classmates together, a class is placed in the same event if
+    /// one has enough seats for all of them, otherwise its students are placed one by one.
+    /// Multi-round events are never assigned.
+    ///
+    /// With `dry_run` nothing is saved, running it again with the same seed (and no registration
+    /// in between) saves the proposed assignments.
+    pub async fn assign_unregistered(
+        db: &DbConn,
+        options: AssignmentOptions,
+    ) -> Result<AssignmentOutcome, DbErr> {
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+        let transaction = db.begin().await?;
+
+        let rounds = Rounds::load(&transaction).await?;
+
+        let max_users = round_max_users::Entity::find()
+            .filter(round_max_users::Column::Round.is_in(rounds.ids()))
+            .order_by_asc(round_max_users::Column::EventId)
+            .order_by_asc(round_max_users::Column::Round)
+            .lock_exclusive()
+            .all(&transaction)
+            .await?;
+
+        let students = user::Entity::find()
+            .filter(user::Column::Role.eq(UserRole::Student))
+            .filter(user::Column::Active.eq(true))
+            .filter(
+                user::Column::Id.not_in_subquery(
+                    sea_query::Query::select()
+                        .column(event_user::Column::UserId)
+                        .from(event_user::Entity)
+                        .and_where(event_user::Column::Round.is_in(rounds.ids()))
+                        .to_owned(),
+                ),
+            )
+            .order_by_asc(user::Column::Id)
+            .all(&transaction)
+            .await?;
+
+        let minimum_sections = event::Entity::find()
+            .filter(in_active_edition(event::Column::EditionId))
+            .filter(event::Column::RoundSpan.eq(1))
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|event| (event.id, event.minimum_section))
+            .collect::<HashMap<_, _>>();
+
+        let mut seats_left = Self::seats_left(&transaction, &max_users).await?;
+
+        // The groups of students to place together, sorted so that the draw only depends on
+        // the seed
+        let mut groups: BTreeMap<String, Vec<&user::Model>> = BTreeMap::new();
+        let mut alone = Vec::new();
+
+        for student in &students {
+            match &student.class {
+                Some(class) if options.keep_classmates_together => {
+                    groups.entry(class.clone()).or_default().push(student)
+                }
+                _ => alone.push(vec![student]),
+            }
+        }
+
+        let groups = groups.into_values().chain(alone).collect::<Vec<_>>();
+
+        let mut outcome = AssignmentOutcome {
+            seed,
+            ..Default::default()
+        };
+
+        // The events every student already got, to avoid placing it twice in the same event
+        let mut taken: HashMap<i32, Vec<i32>> = HashMap::new();
+
+        for round in rounds.ids() {
+            let mut order = groups.iter().collect::<Vec<_>>();
+            order.shuffle(&mut rng);
+
+            for group in order {
+                let event_id = Self::best_event(
+                    round,
+                    group,
+                    &minimum_sections,
+                    &seats_left,
+                    &taken,
+                    options.rules,
+                );
+
+                let placements = match event_id {
+                    Some(event_id) => group
+                        .iter()
+                        .map(|student| (*student, Some(event_id)))
+                        .collect::<Vec<_>>(),
+                    None => group
+                        .iter()
+                        .map(|student| {
+                            let event_id = Self::best_event(
+                                round,
+                                &[*student],
+                                &minimum_sections,
+                                &seats_left,
+                                &taken,
+                                options.rules,
+                            );
+
+                            if let Some(event_id) = event_id {
+                                if let Some(left) = seats_left.get_mut(&(round, event_id)) {
+                                    *left -= 1;
+                                }
+                            }
+
+                            (*student, event_id)
+                        })
+                        .collect::<Vec<_>>(),
+                };
+
+                if let Some(event_id) = event_id {
+                    if let Some(left) = seats_left.get_mut(&(round, event_id)) {
+                        *left -= group.len() as i64;
+                    }
+                }
+
+                for (student, event_id) in placements {
+                    match event_id {
+                        Some(event_id) => {
+                            taken.entry(student.id).or_default().push(event_id);
+
+                            outcome.assigned.push(AssignedSeat {
+                                user_id: student.id,
+                                round,
+                                event_id,
+                            });
+                        }
+                        None => outcome.unassigned.push(UnallocatedSeat {
+                            user_id: student.id,
+                            round,
+                        }),
+                    }
+                }
+            }
+        }
+
+        if options.dry_run || outcome.assigned.is_empty() {
+            transaction.rollback().await?;
+            return Ok(outcome);
+        }
+
+        let rows = outcome
+            .assigned
+            .iter()
+            .map(|seat| event_user::ActiveModel {
+                user_id: Set(seat.user_id),
+                joined_at: Default::default(),
+                left_at: Default::default(),
+                event_id: Set(seat.event_id),
+                round: Set(seat.round),
+            })
+            .collect::<Vec<_>>();
+
+        event_user::Entity::insert_many(rows)
+            .exec(&transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        outcome.applied = true;
+
+        Ok(outcome)
+    }
+
+    /// Groups the preferences by round and by student, keeping their order
+    fn ranked_rounds(preferences: Vec<event_preference::Model>) -> RankedRounds {
+        let mut ranked_rounds = RankedRounds::new();
+
+        for preference in preferences {
+            ranked_rounds
+                .entry(preference.round)
+                .or_default()
+                .entry(preference.user_id)
+                .or_default()
+                .push(preference);
+        }
+
+        ranked_rounds
+    }
+
+    /// Splits the seats held by the students between the ones released by the allocation, the
+    /// seats of the rounds they ranked with every round of their multi-round events, and the
+    /// ones kept
+    fn release_ranked_rounds(
+        ranked_rounds: &RankedRounds,
+        events: &HashMap<i32, event::Model>,
+        rounds: &Rounds,
+        held: Vec<event_user::Model>,
+    ) -> (HeldSeats, HeldSeats) {
+        let mut kept = HeldSeats::new();
+        for seat in held {
+            kept.entry(seat.user_id)
+                .or_default()
+                .insert(seat.round, seat.event_id);
+        }
+
+        let mut released = HeldSeats::new();
+
+        for (&round, students) in ranked_rounds {
+            for user_id in students.keys() {
+                let Some(held_by_user) = kept.get_mut(user_id) else {
+                    continue;
+                };
+                let Some(&event_id) = held_by_user.get(&round) else {
+                    continue;
+                };
+
+                let span = events.get(&event_id).map_or(1, |event| event.round_span);
+
+                // The booking the round belongs to, the round alone when it isn't whole
+                let booking = rounds
+                    .ids()
+                    .filter_map(|start| rounds.spanned_from(start, span))
+                    .find(|spanned| {
+                        spanned.contains(&round)
+                            && spanned
+                                .iter()
+                                .all(|round| held_by_user.get(round) == Some(&event_id))
+                    })
+                    .unwrap_or_else(|| vec![round]);
+
+                for round in booking {
+                    held_by_user.remove(&round);
+                    released
+                        .entry(*user_id)
+                        .or_default()
+                        .insert(round, event_id);
+                }
+            }
+        }
+
+        kept.retain(|_, rounds| !rounds.is_empty());
+
+        (released, kept)
+    }
+
+    /// The draw of `allocate_preferences`, from the preferences ordered by rank, the sections of
+    /// the students, the events of the edition, the seats the students keep and the seats left
+    /// for every event in every round. It only depends on its inputs, the same seed gives the
+    /// same seats.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_seats(
+        ranked_rounds: &RankedRounds,
+        sections: &HashMap<i32, i32>,
+        events: &HashMap<i32, event::Model>,
+        rounds: &Rounds,
+        kept: HeldSeats,
+        mut seats_left: HashMap<(i32, i32), i64>,
+        rules: SubscriptionRules,
+        seed: u64,
+    ) -> AllocationOutcome {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+        let mut outcome = AllocationOutcome {
+            seed,
+            ..Default::default()
+        };
+
+        // The events of every student by round, the kept ones and the ones given
+        let mut given = kept;
+
+        for (&round, students) in ranked_rounds {
+            let mut order = students
+                .keys()
+                .copied()
+                .filter(|user_id| {
+                    // Already given by a multi-round event of a previous round
+                    !given
+                        .get(user_id)
+                        .is_some_and(|rounds| rounds.contains_key(&round))
+                })
+                .collect::<Vec<_>>();
+            order.shuffle(&mut rng);
+
+            for user_id in order {
+                let section = sections.get(&user_id).copied().unwrap_or(0);
+                let given_to_user = given.entry(user_id).or_default();
+
+                let seat = students[&user_id].iter().find_map(|preference| {
+                    let event = events.get(&preference.event_id)?;
+
+                    let spanned = rounds.spanned_from(round, event.round_span)?;
+
+                    let eligible = event.minimum_section <= section;
+                    let repeated = !rules.allow_repeated_events
+                        && given_to_user.values().any(|event_id| *event_id == event.id);
+                    let free = spanned.iter().all(|round| {
+                        seats_left
+                            .get(&(*round, event.id))
+                            .is_some_and(|left| *left > 0)
+                    });
+                    // The next rounds must be ranked, so that their subscriptions were removed
+                    let next_rounds_available = spanned.iter().skip(1).all(|round| {
+                        !given_to_user.contains_key(round)
+                            && ranked_rounds
+                                .get(round)
+                                .is_some_and(|students| students.contains_key(&user_id))
+                    });
+
+                    (eligible && !repeated && free && next_rounds_available)
+                        .then_some((preference, spanned))
+                });
+
+                match seat {
+                    Some((preference, spanned)) => {
+                        for round in spanned {
+                            if let Some(left) = seats_left.get_mut(&(round, preference.event_id)) {
+                                *left -= 1;
+                            }
+
+                            given_to_user.insert(round, preference.event_id);
+
+                            outcome.allocated.push(AllocatedSeat {
+                                user_id,
+                                round,
+                                event_id: preference.event_id,
+                                rank: preference.rank,
+                            });
+                        }
+                    }
+                    None => outcome.unallocated.push(UnallocatedSeat { user_id, round }),
+                }
+            }
+        }
+
+        outcome
+    }
+
+    /// The event of the round with enough seats for the whole group that every student of the
+    /// group is eligible for, preferring the events none of them has yet, then the emptiest
+    fn best_event(
+        round: i32,
+        group: &[&user::Model],
+        minimum_sections: &HashMap<i32, i32>,
+        seats_left: &HashMap<(i32, i32), i64>,
+        taken: &HashMap<i32, Vec<i32>>,
+        rules: SubscriptionRules,
+    ) -> Option<i32> {
+        let section = group.iter().map(|student| student.section).min()?;
+
+        let already_taken = |event_id: &i32| {
+            group.iter().any(|student| {
+                taken
+                    .get(&student.id)
+                    .is_some_and(|events| events.contains(event_id))
+            })
+        };
+
+        seats_left
+            .iter()
+            .filter(|((seat_round, event_id), left)| {
+                *seat_round == round
+                    && **left >= group.len() as i64
+                    && minimum_sections
+                        .get(event_id)
+                        .is_some_and(|minimum_section| *minimum_section <= section)
+                    && (rules.allow_repeated_events || !already_taken(event_id))
+            })
+            .min_by_key(|((_, event_id), left)| (already_taken(event_id), -**left, *event_id))
+            .map(|((_, event_id), _)| *event_id)
+    }
+
+    /// The seats left for every event in every round, from the capacities and the current
+    /// subscriptions
+    async fn seats_left<C: ConnectionTrait>(
+        db: &C,
+        max_users: &[round_max_users::Model],
+    ) -> Result<HashMap<(i32, i32), i64>, DbErr> {
+        let counts = event_user::Entity::find()
+            .select_only()
+            .column(event_user::Column::Round)
+            .column(event_user::Column::EventId)
+            .column_as(event_user::Column::UserId.count(), "count")
+            .group_by(event_user::Column::Round)
+            .group_by(event_user::Column::EventId)
+            .into_tuple::<(i32, i32, i64)>()
+            .all(db)
+            .await?;
+
+        let seats_left = max_users
+            .iter()
+            .map(|m| {
+                let taken = counts
+                    .iter()
+                    .find(|(round, event_id, _)| *round == m.round && *event_id == m.event_id)
+                    .map(|(_, _, count)| *count)
+                    .unwrap_or(0);
+
+                ((m.round, m.event_id), m.max_users as i64 - taken)
+            })
+            .collect();
+
+        Ok(seats_left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::entity::round;
+    use sea_orm::prelude::{Date, Time};
+
+    /// Two days of two rounds, numbered from 1
+    fn rounds() -> Rounds {
+        let round = |id: i32, day: u32, hour: u32| round::Model {
+            id,
+            day: Date::from_ymd_opt(2024, 2, day).unwrap(),
+            start_time: Time::from_hms_opt(hour, 30, 0).unwrap(),
+            end_time: Time::from_hms_opt(hour + 2, 30, 0).unwrap(),
+            label: format!("Round {id}"),
+            edition_id: 1,
+        };
+
+        vec![
+            round(1, 22, 8),
+            round(2, 22, 10),
+            round(3, 23, 8),
+            round(4, 23, 10),
+        ]
+        .into()
+    }
+
+    fn event(id: i32, minimum_section: i32, round_span: i32) -> (i32, event::Model) {
+        let event = event::Model {
+            id,
+            name: format!("Event {id}"),
+            room: String::new(),
+            zone: String::new(),
+            floor: String::new(),
+            minimum_section,
+            round_span,
+            edition_id: 1,
+        };
+
+        (id, event)
+    }
+
+    /// The preferences of a student in a round, the most wanted event first
+    fn ranked(user_id: i32, round: i32, event_ids: &[i32]) -> Vec<event_preference::Model> {
+        (1..)
+            .zip(event_ids)
+            .map(|(rank, &event_id)| event_preference::Model {
+                user_id,
+                round,
+                rank,
+                event_id,
+            })
+            .collect()
+    }
+
+    /// The given capacity for every event in every round
+    fn capacities(event_ids: &[i32], max_users: i64) -> HashMap<(i32, i32), i64> {
+        (1..=4)
+            .flat_map(|round| event_ids.iter().map(move |&event_id| (round, event_id)))
+            .map(|seat| (seat, max_users))
+            .collect()
+    }
+
+    fn draw(
+        preferences: Vec<event_preference::Model>,
+        sections: &HashMap<i32, i32>,
+        events: &HashMap<i32, event::Model>,
+        seats_left: HashMap<(i32, i32), i64>,
+        seed: u64,
+    ) -> AllocationOutcome {
+        draw_keeping(
+            preferences,
+            sections,
+            events,
+            HeldSeats::new(),
+            seats_left,
+            seed,
+        )
+    }
+
+    fn draw_keeping(
+        preferences: Vec<event_preference::Model>,
+        sections: &HashMap<i32, i32>,
+        events: &HashMap<i32, event::Model>,
+        kept: HeldSeats,
+        seats_left: HashMap<(i32, i32), i64>,
+        seed: u64,
+    ) -> AllocationOutcome {
+        Allocation::draw_seats(
+            &Allocation::ranked_rounds(preferences),
+            sections,
+            events,
+            &rounds(),
+            kept,
+            seats_left,
+            SubscriptionRules::default(),
+            seed,
+        )
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_seats() {
+        let events = HashMap::from([event(1, 1, 1), event(2, 1, 1), event(3, 1, 1)]);
+        let sections = (1..=30)
+            .map(|user_id| (user_id, 1))
+            .collect::<HashMap<_, _>>();
+        let preferences = || {
+            (1..=30)
+                .flat_map(|user_id| {
+                    (1..=4).flat_map(move |round| ranked(user_id, round, &[1, 2, 3]))
+                })
+                .collect::<Vec<_>>()
+        };
+        let seats_left = || capacities(&[1, 2, 3], 5);
+
+        let first = draw(preferences(), &sections, &events, seats_left(), 42);
+        let second = draw(preferences(), &sections, &events, seats_left(), 42);
+        let other = draw(preferences(), &sections, &events, seats_left(), 43);
+
+        assert_eq!(first.seed, 42);
+        assert_eq!(first.allocated, second.allocated);
+        assert_eq!(first.unallocated, second.unallocated);
+        assert_ne!(first.allocated, other.allocated);
+    }
+
+    #[test]
+    fn respects_the_capacity_and_the_minimum_section() {
+        // The event 2 is wanted by everyone, but only open from section 3
+        let events = HashMap::from([event(1, 1, 1), event(2, 3, 1)]);
+        let sections = (1..=10)
+            .map(|user_id| (user_id, (user_id - 1) % 5 + 1))
+            .collect::<HashMap<_, _>>();
+        let preferences = (1..=10)
+            .flat_map(|user_id| ranked(user_id, 1, &[2, 1]))
+            .collect::<Vec<_>>();
+
+        for seed in 0..20 {
+            let outcome = draw(
+                preferences.clone(),
+                &sections,
+                &events,
+                capacities(&[1, 2], 3),
+                seed,
+            );
+
+            let seats = |event_id: i32| {
+                outcome
+                    .allocated
+                    .iter()
+                    .filter(move |seat| seat.event_id == event_id)
+            };
+
+            assert_eq!(seats(1).count(), 3);
+            assert_eq!(seats(2).count(), 3);
+            assert!(seats(2).all(|seat| sections[&seat.user_id] >= 3));
+            assert_eq!(outcome.unallocated.len(), 4);
+        }
+    }
+
+    #[test]
+    fn multi_round_events_take_the_following_rounds() {
+        // The event 1 lasts two rounds, the event 2 one
+        let events = HashMap::from([event(1, 1, 2), event(2, 1, 1)]);
+        let sections = HashMap::from([(1, 1), (2, 1), (3, 1)]);
+        let preferences = [
+            // Ranked in both rounds of the first day
+            ranked(1, 1, &[1, 2]),
+            ranked(1, 2, &[2]),
+            // The second round isn't ranked, its subscription would be kept
+            ranked(2, 1, &[1, 2]),
+            // The event would span the two days
+            ranked(3, 2, &[1, 2]),
+            ranked(3, 3, &[2]),
+        ]
+        .concat();
+
+        let outcome = draw(preferences, &sections, &events, capacities(&[1, 2], 5), 7);
+
+        let given = |user_id: i32| {
+            outcome
+                .allocated
+                .iter()
+                .filter(|seat| seat.user_id == user_id)
+                .map(|seat| (seat.round, seat.event_id))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(given(1), vec![(1, 1), (2, 1)]);
+        assert_eq!(given(2), vec![(1, 2)]);
+        assert_eq!(given(3), vec![(2, 2)]);
+        // The event 2 was already given in the second round
+        assert_eq!(
+            outcome.unallocated,
+            vec![UnallocatedSeat {
+                user_id: 3,
+                round: 3
+            }]
+        );
+    }
+
+    fn seat(user_id: i32, round: i32, event_id: i32) -> event_user::Model {
+        event_user::Model {
+            event_id,
+            user_id,
+            joined_at: None,
+            left_at: None,
+            round,
+        }
+    }
+
+    #[test]
+    fn releases_the_whole_of_the_partially_ranked_bookings() {
+        // The event 1 lasts two rounds, the event 2 one
+        let events = HashMap::from([event(1, 1, 2), event(2, 1, 1)]);
+        let preferences = Allocation::ranked_rounds(
+            [
+                // Only the first round of the booking is ranked
+                ranked(1, 1, &[2]),
+                // Only the second round of the booking is ranked, the other day is kept
+                ranked(2, 2, &[2]),
+            ]
+            .concat(),
+        );
+        let held = vec![
+            seat(1, 1, 1),
+            seat(1, 2, 1),
+            seat(1, 3, 2),
+            seat(2, 1, 1),
+            seat(2, 2, 1),
+            seat(2, 3, 1),
+            seat(2, 4, 1),
+        ];
+
+        let (released, kept) =
+            Allocation::release_ranked_rounds(&preferences, &events, &rounds(), held);
+
+        assert_eq!(
+            released,
+            HashMap::from([
+                (1, BTreeMap::from([(1, 1), (2, 1)])),
+                (2, BTreeMap::from([(1, 1), (2, 1)])),
+            ])
+        );
+        assert_eq!(
+            kept,
+            HashMap::from([
+                (1, BTreeMap::from([(3, 2)])),
+                (2, BTreeMap::from([(3, 1), (4, 1)])),
+            ])
+        );
+    }
+
+    #[test]
+    fn the_kept_seats_count_against_the_events_given() {
+        // The event 3 lasts two rounds
+        let events = HashMap::from([
+            event(1, 1, 1),
+            event(2, 1, 1),
+            event(3, 1, 2),
+            event(4, 1, 1),
+        ]);
+        let sections = HashMap::from([(1, 1)]);
+        // The event 1 is kept in the second round, the event 3 can't book it
+        let kept = HashMap::from([(1, BTreeMap::from([(2, 1)]))]);
+        let preferences = [ranked(1, 1, &[3, 1, 2]), ranked(1, 3, &[2, 1, 4])].concat();
+
+        for seed in 0..10 {
+            let outcome = draw_keeping(
+                preferences.clone(),
+                &sections,
+                &events,
+                kept.clone(),
+                capacities(&[1, 2, 3, 4], 5),
+                seed,
+            );
+
+            let given = outcome
+                .allocated
+                .iter()
+                .map(|seat| (seat.round, seat.event_id))
+                .collect::<Vec<_>>();
+
+            assert_eq!(given, vec![(1, 2), (3, 4)]);
+        }
+    }
+}
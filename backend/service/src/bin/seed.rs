@@ -0,0 +1,343 @@
+use chrono::{NaiveDate, NaiveTime};
+use clap::Parser;
+use color_eyre::eyre::{bail, WrapErr};
+use color_eyre::Result;
+use std::path::{Path, PathBuf};
+use svelte_rust_event_scheduler_service::sea_orm::{Database, DatabaseConnection};
+use svelte_rust_event_scheduler_service::{
+    EventColumns, EventImport, EventImportOptions, Mutation, NewEdition, NewRound, Query,
+    RosterImport, RosterImportOptions, RoundColumn, Sections, SubscriptionRules,
+    DEFAULT_MAX_SECTION,
+};
+
+/// Imports the data of a school, once the schema is up to date
+#[derive(Debug, Parser)]
+struct SeedCli {
+    #[arg(short = 'u', long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: String,
+
+    /// Create an edition with the rounds of this CSV file (day as YYYY-MM-DD, start_time and
+    /// end_time as HH:MM, label) and make it the active one
+    #[arg(long)]
+    rounds: Option<PathBuf>,
+
+    /// The name of the edition created from the rounds
+    #[arg(long, default_value = "Forum", requires = "rounds")]
+    edition: String,
+
+    /// Set the day, time and label of the rounds of the active edition from the rounds, in
+    /// chronological order, instead of creating an edition. The placeholder rounds of the
+    /// databases that had registrations before the rounds were stored are set this way.
+    #[arg(long, requires = "rounds", conflicts_with = "edition")]
+    update_rounds: bool,
+
+    /// Import the events of the active edition from this xlsx workbook, its columns are set by
+    /// the following options
+    #[arg(long)]
+    events: Option<PathBuf>,
+
+    #[command(flatten)]
+    event_columns: EventColumnArgs,
+
+    /// Import the students from this CSV roster, with the name, email, section and class columns
+    #[arg(long)]
+    roster: Option<PathBuf>,
+
+    /// Deactivate the students missing from the roster
+    #[arg(long, requires = "roster")]
+    deactivate_missing: bool,
+
+    /// The highest section of the school, the sections of the students and the minimum
+    /// sections of the events must be between 1 and it
+    #[arg(long, env = "MAX_SECTION", default_value_t = DEFAULT_MAX_SECTION,
+          value_parser = clap::value_parser!(i32).range(1..))]
+    max_section: i32,
+
+    /// Whether a student can attend the same event in several rounds, the students promoted
+    /// from the waitlists by the imports follow it
+    #[arg(long, env = "ALLOW_REPEATED_EVENTS")]
+    allow_repeated_events: bool,
+}
+
+/// The headers of the columns of the events workbook, matched ignoring case
+#[derive(Debug, clap::Args)]
+struct EventColumnArgs {
+    /// The sheet of the events, the first one when not set
+    #[arg(long, requires = "events")]
+    sheet: Option<String>,
+
+    #[arg(long, default_value = "name")]
+    name_column: String,
+
+    #[arg(long, default_value = "room")]
+    room_column: String,
+
+    #[arg(long, default_value = "zone")]
+    zone_column: String,
+
+    #[arg(long, default_value = "floor")]
+    floor_column: String,
+
+    /// Empty when the workbook has none, new events are then open from section 1
+    #[arg(long, default_value = "minimum_section")]
+    minimum_section_column: String,
+
+    /// Empty when the workbook has none, new events then last one round
+    #[arg(long, default_value = "round_span")]
+    round_span_column: String,
+
+    /// The capacity of the rounds marked with an X, empty when the workbook has none
+    #[arg(long, default_value = "max_users")]
+    max_users_column: String,
+
+    /// The round an event marked with a single X runs in, when its column holds several rounds
+    #[arg(long)]
+    turn_column: Option<String>,
+
+    /// The column of a round as POSITION=HEADER, the position of the round in the active
+    /// edition starting from 1. Several rounds can share a column, like the ones of a day, they
+    /// are then numbered by turn in the order they are given. Every round is headed by its label
+    /// when not set.
+    #[arg(long = "round-column", value_parser = parse_round_column)]
+    round_columns: Vec<(usize, String)>,
+}
+
+fn parse_round_column(value: &str) -> Result<(usize, String), String> {
+    let (position, column) = value
+        .split_once('=')
+        .ok_or_else(|| format!("{value:?} is not POSITION=HEADER"))?;
+    let position = position
+        .trim()
+        .parse()
+        .map_err(|_| format!("{position:?} is not the position of a round"))?;
+
+    Ok((position, column.to_string()))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    dotenvy::dotenv().ok();
+
+    let cli = SeedCli::parse();
+    let sections = Sections {
+        max: cli.max_section,
+    };
+    let rules = SubscriptionRules {
+        allow_repeated_events: cli.allow_repeated_events,
+    };
+
+    let db = Database::connect(&cli.database_url)
+        .await
+        .wrap_err("Failed to connect to the database")?;
+
+    if let Some(path) = &cli.rounds {
+        let rounds = read_rounds(path)?;
+
+        if cli.update_rounds {
+            update_rounds(&db, rounds).await?;
+        } else {
+            seed_edition(&db, &cli.edition, rounds).await?;
+        }
+    }
+
+    if let Some(path) = &cli.events {
+        seed_events(&db, path, &cli.event_columns, sections, rules).await?;
+    }
+
+    if let Some(path) = &cli.roster {
+        seed_roster(&db, path, cli.deactivate_missing, sections, rules).await?;
+    }
+
+    Ok(())
+}
+
+fn read_rounds(path: &Path) -> Result<Vec<NewRound>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+
+    let mut rounds = Vec::new();
+
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let field = |index: usize| record.get(index).unwrap_or_default();
+
+        rounds.push(NewRound {
+            day: NaiveDate::parse_from_str(field(0), "%Y-%m-%d")
+                .wrap_err_with(|| format!("Invalid day on line {line}"))?,
+            start_time: NaiveTime::parse_from_str(field(1), "%H:%M")
+                .wrap_err_with(|| format!("Invalid start time on line {line}"))?,
+            end_time: NaiveTime::parse_from_str(field(2), "%H:%M")
+                .wrap_err_with(|| format!("Invalid end time on line {line}"))?,
+            label: field(3).to_string(),
+        });
+    }
+
+    Ok(rounds)
+}
+
+async fn seed_edition(db: &DatabaseConnection, name: &str, rounds: Vec<NewRound>) -> Result<()> {
+    let edition = Mutation::create_edition(
+        db,
+        NewEdition {
+            name: name.to_string(),
+            rounds,
+        },
+    )
+    .await?;
+    Mutation::activate_edition(db, edition.id).await?;
+
+    println!("Created and activated the edition {} ({name})", edition.id);
+
+    Ok(())
+}
+
+async fn update_rounds(db: &DatabaseConnection, rounds: Vec<NewRound>) -> Result<()> {
+    let Some(edition) = Query::get_editions(db)
+        .await?
+        .into_iter()
+        .find(|edition| edition.active)
+    else {
+        bail!("There is no active edition to update the rounds of");
+    };
+
+    let rounds = Mutation::update_rounds(db, edition.id, rounds).await?;
+
+    println!(
+        "Updated the {} rounds of the edition {} ({})",
+        rounds.len(),
+        edition.id,
+        edition.name
+    );
+
+    Ok(())
+}
+
+async fn seed_events(
+    db: &DatabaseConnection,
+    path: &Path,
+    event_columns: &EventColumnArgs,
+    sections: Sections,
+    rules: SubscriptionRules,
+) -> Result<()> {
+    let workbook =
+        std::fs::read(path).wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+
+    let rounds = Query::get_rounds(db).await?;
+
+    let round_columns = if event_columns.round_columns.is_empty() {
+        rounds
+            .into_iter()
+            .map(|round| RoundColumn {
+                round: round.id,
+                column: round.label,
+            })
+            .collect()
+    } else {
+        let mut round_columns = Vec::with_capacity(event_columns.round_columns.len());
+        for (position, column) in &event_columns.round_columns {
+            let Some(round) = position.checked_sub(1).and_then(|i| rounds.get(i)) else {
+                bail!(
+                    "The active edition has no round {position}, it has {} of them",
+                    rounds.len()
+                );
+            };
+
+            round_columns.push(RoundColumn {
+                round: round.id,
+                column: column.clone(),
+            });
+        }
+        round_columns
+    };
+
+    let optional_column = |column: &str| Some(column.to_string()).filter(|c| !c.is_empty());
+
+    let columns = EventColumns {
+        name: event_columns.name_column.clone(),
+        room: event_columns.room_column.clone(),
+        zone: event_columns.zone_column.clone(),
+        floor: event_columns.floor_column.clone(),
+        minimum_section: optional_column(&event_columns.minimum_section_column),
+        round_span: optional_column(&event_columns.round_span_column),
+        max_users: optional_column(&event_columns.max_users_column),
+        turn: event_columns.turn_column.clone(),
+        rounds: round_columns,
+    };
+
+    let outcome = EventImport::import_xlsx(
+        db,
+        workbook,
+        EventImportOptions {
+            sheet: event_columns.sheet.clone(),
+            columns,
+            rules,
+            sections,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    if !outcome.errors.is_empty() {
+        for error in &outcome.errors {
+            eprintln!(
+                "{}, row {}, column {:?}: {}",
+                path.display(),
+                error.row,
+                error.column,
+                error.message
+            );
+        }
+        bail!("The events have invalid rows, nothing was imported");
+    }
+
+    println!(
+        "Imported the events: {} created, {} updated",
+        outcome.created, outcome.updated
+    );
+
+    Ok(())
+}
+
+async fn seed_roster(
+    db: &DatabaseConnection,
+    path: &Path,
+    deactivate_missing: bool,
+    sections: Sections,
+    rules: SubscriptionRules,
+) -> Result<()> {
+    let roster =
+        std::fs::read(path).wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+
+    let outcome = RosterImport::import_csv(
+        db,
+        &roster,
+        RosterImportOptions {
+            deactivate_missing,
+            rules,
+            sections,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    if !outcome.errors.is_empty() {
+        for error in &outcome.errors {
+            eprintln!("{}, row {}: {}", path.display(), error.row, error.message);
+        }
+        bail!("The roster has invalid rows, nothing was imported");
+    }
+
+    println!(
+        "Imported the roster: {} created, {} updated, {} unchanged, {} deactivated",
+        outcome.created,
+        outcome.updated,
+        outcome.unchanged,
+        outcome.deactivated.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,370 @@
+use crate::edition::in_active_edition;
+use crate::query::contains_ignoring_case;
+use crate::{EventSubscription, Mutation, Query, Rounds, SubscriptionRules, WaitlistPromotion};
+use ::entity::{event, event_user, round_max_users};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::*;
+use std::collections::{BTreeSet, HashMap};
+
+/// The capacity of an event in a round, 0 when it doesn't run in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventCapacity {
+    pub event_id: i32,
+    pub event_name: String,
+    pub round: i32,
+    pub max_users: i32,
+    /// The students subscribed to the event in the round
+    pub registered: u64,
+}
+
+/// Selects the capacities of the events of the active edition, the empty fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct CapacityFilter {
+    pub event_ids: Vec<i32>,
+    /// Matched ignoring case
+    pub name_contains: Option<String>,
+    pub rounds: Vec<i32>,
+}
+
+impl CapacityFilter {
+    fn selects_events(&self) -> bool {
+        !self.event_ids.is_empty() || self.name_contains.is_some()
+    }
+}
+
+/// A student removed from an event whose capacity was lowered below its subscriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BumpedStudent {
+    pub user_id: i32,
+    pub round: i32,
+    pub event_id: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapacityOptions {
+    /// Bump the students above the new capacity, nothing is saved when some would be otherwise
+    pub force: bool,
+    /// Only report the changes and the students that would be bumped, without saving them
+    pub dry_run: bool,
+    /// The rules the waitlisted students taking the added seats must follow
+    pub rules: SubscriptionRules,
+}
+
+#[derive(Debug, Default)]
+pub struct CapacityChanges {
+    /// The capacities after the change, with the students registered once the change is applied
+    pub changed: Vec<EventCapacity>,
+    /// The students bumped, or that would be bumped when the change isn't applied
+    pub bumped: Vec<BumpedStudent>,
+    /// The waitlisted students that took the seats added by the change
+    pub promotions: Vec<WaitlistPromotion>,
+    /// Whether the capacities were saved
+    pub applied: bool,
+}
+
+impl CapacityChanges {
+    /// The events whose capacity or subscriber count changed
+    pub fn affected_event_ids(&self) -> Vec<i32> {
+        let mut event_ids = self
+            .changed
+            .iter()
+            .map(|c| c.event_id)
+            .chain(self.bumped.iter().map(|s| s.event_id))
+            .chain(
+                self.promotions
+                    .iter()
+                    .flat_map(|p| std::iter::once(p.event_id).chain(p.previous_event_id)),
+            )
+            .collect::<Vec<_>>();
+
+        event_ids.sort_unstable();
+        event_ids.dedup();
+
+        event_ids
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CapacityError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("The capacity can't be negative, got {0}")]
+    NegativeCapacity(i32),
+
+    #[error("Select the events to change, by id or by name")]
+    NoEventsSelected,
+
+    #[error("Round {0} is not a round of the active edition")]
+    UnknownRound(i32),
+}
+
+impl Query {
+    /// Returns the capacity of the selected events in the selected rounds, by event and round
+    pub async fn get_capacities(
+        db: &DbConn,
+        filter: &CapacityFilter,
+    ) -> Result<Vec<EventCapacity>, CapacityError> {
+        capacities(db, filter, false).await
+    }
+}
+
+impl Mutation {
+    /// Sets the capacity of the selected events in the selected rounds, creating the missing
+    /// `round_max_users` rows. The selected `round_max_users` rows are locked until the end of
+    /// the transaction.
+    ///
+    /// Lowering a capacity below the registered students only reports the students that would
+    /// be bumped, unless forced: the students that didn't join the event yet are bumped first,
+    /// the most recent accounts first. A student bumped from a multi-round event leaves every
+    /// round of it. The seats added are offered to the waitlisted students.
+    pub async fn set_capacities(
+        db: &DbConn,
+        filter: &CapacityFilter,
+        max_users: i32,
+        options: CapacityOptions,
+    ) -> Result<CapacityChanges, CapacityError> {
+        if max_users < 0 {
+            return Err(CapacityError::NegativeCapacity(max_users));
+        }
+
+        if !filter.selects_events() {
+            return Err(CapacityError::NoEventsSelected);
+        }
+
+        let transaction = db.begin().await?;
+
+        let current = capacities(&transaction, filter, true).await?;
+
+        let round_spans = event::Entity::find()
+            .filter(event::Column::Id.is_in(current.iter().map(|c| c.event_id)))
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|event| (event.id, event.round_span))
+            .collect::<HashMap<_, _>>();
+
+        let mut bumped = BTreeSet::new();
+
+        for capacity in current.iter().filter(|c| c.registered > max_users as u64) {
+            // A student bumped from another round of a multi-round event already freed a seat
+            let already_bumped = bumped
+                .iter()
+                .filter(|s: &&BumpedStudent| {
+                    s.event_id == capacity.event_id && s.round == capacity.round
+                })
+                .map(|s| s.user_id)
+                .collect::<Vec<_>>();
+
+            let to_bump = capacity.registered - max_users as u64;
+            if already_bumped.len() as u64 >= to_bump {
+                continue;
+            }
+
+            let students = event_user::Entity::find()
+                .filter(event_user::Column::EventId.eq(capacity.event_id))
+                .filter(event_user::Column::Round.eq(capacity.round))
+                .filter(event_user::Column::UserId.is_not_in(already_bumped.iter().copied()))
+                .order_by(
+                    Expr::col(event_user::Column::JoinedAt).is_not_null(),
+                    Order::Asc,
+                )
+                .order_by_desc(event_user::Column::UserId)
+                .limit(to_bump - already_bumped.len() as u64)
+                .all(&transaction)
+                .await?;
+
+            for student in students {
+                bumped.insert(BumpedStudent {
+                    user_id: student.user_id,
+                    round: student.round,
+                    event_id: student.event_id,
+                });
+
+                if round_spans.get(&student.event_id).copied().unwrap_or(1) > 1 {
+                    let other_rounds = event_user::Entity::find()
+                        .filter(event_user::Column::UserId.eq(student.user_id))
+                        .filter(event_user::Column::EventId.eq(student.event_id))
+                        .all(&transaction)
+                        .await?;
+
+                    bumped.extend(other_rounds.into_iter().map(|s| BumpedStudent {
+                        user_id: s.user_id,
+                        round: s.round,
+                        event_id: s.event_id,
+                    }));
+                }
+            }
+        }
+
+        let bumped = bumped.into_iter().collect::<Vec<_>>();
+
+        for student in &bumped {
+            event_user::Entity::delete_by_id((student.user_id, student.round))
+                .exec(&transaction)
+                .await?;
+        }
+
+        if !current.is_empty() {
+            round_max_users::Entity::insert_many(current.iter().map(|capacity| {
+                round_max_users::ActiveModel {
+                    round: Set(capacity.round),
+                    event_id: Set(capacity.event_id),
+                    max_users: Set(max_users),
+                }
+            }))
+            .on_conflict(
+                OnConflict::columns([
+                    round_max_users::Column::Round,
+                    round_max_users::Column::EventId,
+                ])
+                .update_column(round_max_users::Column::MaxUsers)
+                .to_owned(),
+            )
+            .exec(&transaction)
+            .await?;
+        }
+
+        // The seats freed by the bumped students in the other rounds of multi-round events, and
+        // the ones added by a higher capacity
+        let freed =
+            bumped
+                .iter()
+                .map(|student| EventSubscription {
+                    round: student.round,
+                    event_id: student.event_id,
+                })
+                .chain(current.iter().filter(|c| c.max_users < max_users).map(|c| {
+                    EventSubscription {
+                        round: c.round,
+                        event_id: c.event_id,
+                    }
+                }))
+                .collect::<Vec<_>>();
+
+        let promotions = Self::promote_waitlisted(&transaction, &freed, options.rules).await?;
+
+        let changed = capacities(&transaction, filter, false).await?;
+
+        let applied = !options.dry_run && (options.force || bumped.is_empty());
+        if applied {
+            transaction.commit().await?;
+        } else {
+            transaction.rollback().await?;
+        }
+
+        Ok(CapacityChanges {
+            changed,
+            bumped,
+            promotions,
+            applied,
+        })
+    }
+}
+
+/// The events of the active edition selected by the filter
+fn events_query(filter: &CapacityFilter) -> Select<event::Entity> {
+    event::Entity::find()
+        .filter(in_active_edition(event::Column::EditionId))
+        .apply_if(
+            Some(&filter.event_ids).filter(|ids| !ids.is_empty()),
+            |query, ids| query.filter(event::Column::Id.is_in(ids.iter().copied())),
+        )
+        .apply_if(filter.name_contains.as_deref(), |query, name| {
+            query.filter(contains_ignoring_case(event::Column::Name, name))
+        })
+        .order_by_asc(event::Column::Id)
+}
+
+/// The capacities of the selected events in the selected rounds, with the registered students
+/// counted by a single grouped query
+async fn capacities<C: ConnectionTrait>(
+    db: &C,
+    filter: &CapacityFilter,
+    lock: bool,
+) -> Result<Vec<EventCapacity>, CapacityError> {
+    let rounds = Rounds::load(db).await?;
+
+    if let Some(&round) = filter.rounds.iter().find(|&&r| !rounds.contains(r)) {
+        return Err(CapacityError::UnknownRound(round));
+    }
+
+    let round_ids = if filter.rounds.is_empty() {
+        rounds.ids().collect::<Vec<_>>()
+    } else {
+        rounds
+            .ids()
+            .filter(|round| filter.rounds.contains(round))
+            .collect()
+    };
+
+    let events = events_query(filter).all(db).await?;
+
+    let event_ids = events.iter().map(|event| event.id).collect::<Vec<_>>();
+
+    let mut max_users_query = round_max_users::Entity::find()
+        .filter(round_max_users::Column::EventId.is_in(event_ids.iter().copied()))
+        .filter(round_max_users::Column::Round.is_in(round_ids.iter().copied()))
+        .order_by_asc(round_max_users::Column::EventId)
+        .order_by_asc(round_max_users::Column::Round);
+
+    if lock {
+        max_users_query = max_users_query.lock_exclusive();
+    }
+
+    let max_users = max_users_query
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|m| ((m.event_id, m.round), m.max_users))
+        .collect::<HashMap<_, _>>();
+
+    let registered = event_user::Entity::find()
+        .select_only()
+        .column(event_user::Column::EventId)
+        .column(event_user::Column::Round)
+        .column_as(event_user::Column::UserId.count(), "count")
+        .filter(event_user::Column::EventId.is_in(event_ids.iter().copied()))
+        .filter(event_user::Column::Round.is_in(round_ids.iter().copied()))
+        .group_by(event_user::Column::EventId)
+        .group_by(event_user::Column::Round)
+        .into_tuple::<(i32, i32, i64)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(event_id, round, count)| ((event_id, round), count as u64))
+        .collect::<HashMap<_, _>>();
+
+    Ok(events
+        .iter()
+        .flat_map(|event| {
+            round_ids.iter().map(|&round| EventCapacity {
+                event_id: event.id,
+                event_name: event.name.clone(),
+                round,
+                max_users: max_users.get(&(event.id, round)).copied().unwrap_or(0),
+                registered: registered.get(&(event.id, round)).copied().unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_wildcards_of_the_name_literally() {
+        let filter = CapacityFilter {
+            name_contains: Some("Lab_1 100%".to_string()),
+            ..Default::default()
+        };
+
+        let sql = events_query(&filter).build(DbBackend::Postgres).to_string();
+
+        assert!(
+            sql.contains(r"LIKE E'%lab\\_1 100\\%%' ESCAPE E'\\'"),
+            "{sql}"
+        );
+    }
+}
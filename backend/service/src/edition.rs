@@ -0,0 +1,232 @@
+use ::entity::{edition, round};
+use chrono::{NaiveDate, NaiveTime, Utc};
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::*;
+
+use crate::{Mutation, Query};
+
+/// A round of a new edition
+#[derive(Debug, Clone)]
+pub struct NewRound {
+    pub day: NaiveDate,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub label: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewEdition {
+    pub name: String,
+    pub rounds: Vec<NewRound>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EditionError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("Edition {0} not found")]
+    NotFound(i32),
+
+    #[error("Edition {0} is archived")]
+    Archived(i32),
+
+    #[error("Edition {0} is the active one, activate another edition before archiving it")]
+    Active(i32),
+
+    #[error("The round {0:?} ends before it starts")]
+    InvalidRound(String),
+
+    #[error("Edition {edition_id} has {expected} rounds, got {got}")]
+    RoundCount {
+        edition_id: i32,
+        expected: usize,
+        got: usize,
+    },
+}
+
+/// Matches the rows whose edition, through the given `edition_id` column, is the active one
+pub(crate) fn in_active_edition(column: impl ColumnTrait) -> SimpleExpr {
+    column.in_subquery(
+        sea_query::Query::select()
+            .column(edition::Column::Id)
+            .from(edition::Entity)
+            .and_where(Expr::col(edition::Column::Active).eq(true))
+            .to_owned(),
+    )
+}
+
+/// Matches the rows whose round, through the given round column, belongs to the active edition
+pub(crate) fn in_active_edition_rounds(column: impl ColumnTrait) -> SimpleExpr {
+    column.in_subquery(
+        sea_query::Query::select()
+            .column(round::Column::Id)
+            .from(round::Entity)
+            .and_where(in_active_edition(round::Column::EditionId))
+            .to_owned(),
+    )
+}
+
+impl Query {
+    /// Returns every edition, the most recent first
+    pub async fn get_editions(db: &DbConn) -> Result<Vec<edition::Model>, DbErr> {
+        edition::Entity::find()
+            .order_by_desc(edition::Column::Id)
+            .all(db)
+            .await
+    }
+}
+
+impl Mutation {
+    /// Creates an inactive edition with its rounds, it has to be activated to be used
+    pub async fn create_edition(
+        db: &DbConn,
+        new_edition: NewEdition,
+    ) -> Result<edition::Model, EditionError> {
+        validate_rounds(&new_edition.rounds)?;
+
+        let transaction = db.begin().await?;
+
+        let edition = edition::ActiveModel {
+            name: Set(new_edition.name),
+            active: Set(false),
+            ..Default::default()
+        }
+        .insert(&transaction)
+        .await?;
+
+        if !new_edition.rounds.is_empty() {
+            round::Entity::insert_many(new_edition.rounds.into_iter().map(|round| {
+                round::ActiveModel {
+                    day: Set(round.day),
+                    start_time: Set(round.start_time),
+                    end_time: Set(round.end_time),
+                    label: Set(round.label),
+                    edition_id: Set(edition.id),
+                    ..Default::default()
+                }
+            }))
+            .exec(&transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(edition)
+    }
+
+    /// Sets the day, the time slot and the label of the rounds of an edition, in chronological
+    /// order, like the placeholder rounds of the databases that had registrations before the
+    /// rounds were stored. The edition must have as many rounds, their subscriptions are kept.
+    pub async fn update_rounds(
+        db: &DbConn,
+        edition_id: i32,
+        rounds: Vec<NewRound>,
+    ) -> Result<Vec<round::Model>, EditionError> {
+        validate_rounds(&rounds)?;
+
+        let transaction = db.begin().await?;
+
+        let current = round::Entity::find()
+            .filter(round::Column::EditionId.eq(edition_id))
+            .order_by_asc(round::Column::Day)
+            .order_by_asc(round::Column::StartTime)
+            .order_by_asc(round::Column::Id)
+            .lock_exclusive()
+            .all(&transaction)
+            .await?;
+
+        if current.len() != rounds.len() {
+            transaction.rollback().await?;
+            return Err(EditionError::RoundCount {
+                edition_id,
+                expected: current.len(),
+                got: rounds.len(),
+            });
+        }
+
+        let mut updated = Vec::with_capacity(rounds.len());
+        for (current, round) in current.into_iter().zip(rounds) {
+            let mut current: round::ActiveModel = current.into();
+            current.day = Set(round.day);
+            current.start_time = Set(round.start_time);
+            current.end_time = Set(round.end_time);
+            current.label = Set(round.label);
+
+            updated.push(current.update(&transaction).await?);
+        }
+
+        transaction.commit().await?;
+
+        Ok(updated)
+    }
+
+    /// Makes the edition the active one: its events, rounds and registration windows are
+    /// the ones used from now on, the data of the other editions is kept untouched
+    pub async fn activate_edition(
+        db: &DbConn,
+        edition_id: i32,
+    ) -> Result<edition::Model, EditionError> {
+        let transaction = db.begin().await?;
+
+        let edition = edition::Entity::find_by_id(edition_id)
+            .lock_exclusive()
+            .one(&transaction)
+            .await?
+            .ok_or(EditionError::NotFound(edition_id))?;
+
+        if edition.archived_at.is_some() {
+            transaction.rollback().await?;
+            return Err(EditionError::Archived(edition_id));
+        }
+
+        // The previous active edition has to be deactivated first, only one can be active
+        edition::Entity::update_many()
+            .col_expr(edition::Column::Active, Expr::value(false))
+            .filter(edition::Column::Active.eq(true))
+            .exec(&transaction)
+            .await?;
+
+        let mut edition: edition::ActiveModel = edition.into();
+        edition.active = Set(true);
+        let edition = edition.update(&transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok(edition)
+    }
+
+    /// Archives a past edition, its attendance is kept but it can't be activated anymore
+    pub async fn archive_edition(
+        db: &DbConn,
+        edition_id: i32,
+    ) -> Result<edition::Model, EditionError> {
+        let edition = edition::Entity::find_by_id(edition_id)
+            .one(db)
+            .await?
+            .ok_or(EditionError::NotFound(edition_id))?;
+
+        if edition.active {
+            return Err(EditionError::Active(edition_id));
+        }
+
+        if edition.archived_at.is_some() {
+            return Ok(edition);
+        }
+
+        let mut edition: edition::ActiveModel = edition.into();
+        edition.archived_at = Set(Some(Utc::now().naive_utc()));
+
+        Ok(edition.update(db).await?)
+    }
+}
+
+fn validate_rounds(rounds: &[NewRound]) -> Result<(), EditionError> {
+    match rounds
+        .iter()
+        .find(|round| round.end_time <= round.start_time)
+    {
+        Some(round) => Err(EditionError::InvalidRound(round.label.clone())),
+        None => Ok(()),
+    }
+}
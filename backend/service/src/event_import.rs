@@ -0,0 +1,961 @@
+use crate::{EventSubscription, Mutation, Rounds, Sections, SubscriptionRules, WaitlistPromotion};
+use ::entity::{edition, event, event_user, round_max_users};
+use calamine::{Data, Range, Reader, Xlsx};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::ops::RangeInclusive;
+
+/// The headers of the columns holding the fields of the events, matched ignoring case
+#[derive(Debug, Clone, Default)]
+pub struct EventColumns {
+    pub name: String,
+    pub room: String,
+    pub zone: String,
+    pub floor: String,
+    /// New events are open from section 1 when not set, existing ones are left unchanged
+    pub minimum_section: Option<String>,
+    /// New events last one round when not set, existing ones are left unchanged
+    pub round_span: Option<String>,
+    /// The capacity used by the rounds marked with an `X`
+    pub max_users: Option<String>,
+    /// The round an event marked with a single `X` runs in, when its column holds several rounds
+    pub turn: Option<String>,
+    pub rounds: Vec<RoundColumn>,
+}
+
+/// The column holding the capacity of the events in a round: a number, `X` for the one of the
+/// `max_users` column, empty when the event doesn't run in the round.
+///
+/// A column can hold several rounds, like the ones of a day, numbered by turn in the order they
+/// are mapped: an `X` for each of them, like `XX`, opens the event in all of them, a single `X`
+/// only in the one of the `turn` column. An event with a single `X` and no turn doesn't run in
+/// any of them, as with the legacy workbooks.
+#[derive(Debug, Clone)]
+pub struct RoundColumn {
+    pub round: i32,
+    pub column: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventImportOptions {
+    /// The first sheet of the workbook when not set
+    pub sheet: Option<String>,
+    pub columns: EventColumns,
+    /// Only validate the rows, without saving them
+    pub dry_run: bool,
+    /// The rules the waitlisted students taking the added seats must follow
+    pub rules: SubscriptionRules,
+    pub sections: Sections,
+}
+
+/// An invalid cell, the rows are numbered as in the spreadsheet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventImportRowError {
+    pub row: u32,
+    pub column: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct EventImportOutcome {
+    pub created: usize,
+    pub updated: usize,
+    /// The events created or updated
+    pub event_ids: Vec<i32>,
+    /// Nothing is saved when a row is invalid
+    pub errors: Vec<EventImportRowError>,
+    /// The waitlisted students that took the seats added by the higher capacities
+    pub promotions: Vec<WaitlistPromotion>,
+    /// Whether the events were saved
+    pub applied: bool,
+}
+
+impl EventImportOutcome {
+    /// The events whose capacity or subscriber count changed
+    pub fn affected_event_ids(&self) -> Vec<i32> {
+        let mut event_ids = self
+            .event_ids
+            .iter()
+            .copied()
+            .chain(
+                self.promotions
+                    .iter()
+                    .flat_map(|p| std::iter::once(p.event_id).chain(p.previous_event_id)),
+            )
+            .collect::<Vec<_>>();
+
+        event_ids.sort_unstable();
+        event_ids.dedup();
+
+        event_ids
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EventImportError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("Failed to read the workbook: {0}")]
+    Workbook(#[from] calamine::XlsxError),
+
+    #[error("The workbook has no sheet {0:?}")]
+    SheetNotFound(String),
+
+    #[error("The sheet has no column {0:?}")]
+    MissingColumn(String),
+
+    #[error("Round {0} is not a round of the active edition")]
+    UnknownRound(i32),
+
+    #[error("Round {0} is mapped to more than one column")]
+    DuplicateRound(i32),
+
+    #[error("There is no active edition to import the events into")]
+    NoActiveEdition,
+}
+
+/// An event read from a valid row
+#[derive(Debug)]
+struct ImportedEvent {
+    row: u32,
+    name: String,
+    room: String,
+    zone: String,
+    floor: String,
+    minimum_section: Option<i32>,
+    round_span: Option<i32>,
+    /// The capacity of the event in every mapped round
+    max_users: Vec<(i32, i32)>,
+}
+
+/// The position of every mapped column in the header
+struct ColumnIndexes {
+    name: usize,
+    room: usize,
+    zone: usize,
+    floor: usize,
+    minimum_section: Option<usize>,
+    round_span: Option<usize>,
+    max_users: Option<usize>,
+    turn: Option<usize>,
+    rounds: Vec<CapacityColumn>,
+}
+
+/// A column holding the capacity of the events in one or more rounds
+struct CapacityColumn {
+    column: String,
+    index: usize,
+    /// In the order they were mapped, the one of the turns
+    rounds: Vec<i32>,
+}
+
+pub struct EventImport;
+
+impl EventImport {
+    /// Create or update the events of the active edition from an xlsx workbook, along with their
+    /// capacity in the mapped rounds. The events are matched by name, the ones missing from the
+    /// workbook and the capacities of the rounds that aren't mapped are left untouched.
+    ///
+    /// Every row is validated first, nothing is saved when one of them is invalid or lowers a
+    /// capacity below the students registered. The events and the capacities are saved in one
+    /// transaction, the seats added to the existing events are offered to the waitlisted
+    /// students.
+    pub async fn import_xlsx(
+        db: &DbConn,
+        workbook: Vec<u8>,
+        options: EventImportOptions,
+    ) -> Result<EventImportOutcome, EventImportError> {
+        let range = Self::read_sheet(workbook, options.sheet.as_deref())?;
+
+        let transaction = db.begin().await?;
+
+        let rounds = Rounds::load(&transaction).await?;
+
+        let (events, errors) =
+            Self::parse_rows(&range, &options.columns, &rounds, options.sections)?;
+
+        if !errors.is_empty() {
+            transaction.rollback().await?;
+            return Ok(EventImportOutcome {
+                errors,
+                ..Default::default()
+            });
+        }
+
+        let edition = edition::Entity::find()
+            .filter(edition::Column::Active.eq(true))
+            .one(&transaction)
+            .await?
+            .ok_or(EventImportError::NoActiveEdition)?;
+
+        let existing = event::Entity::find()
+            .filter(event::Column::EditionId.eq(edition.id))
+            .lock_exclusive()
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|event| (event.name.clone(), event))
+            .collect::<HashMap<_, _>>();
+
+        let existing_ids = existing.values().map(|event| event.id).collect::<Vec<_>>();
+
+        let current_max_users = round_max_users::Entity::find()
+            .filter(round_max_users::Column::EventId.is_in(existing_ids.iter().copied()))
+            .lock_exclusive()
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|m| ((m.event_id, m.round), m.max_users))
+            .collect::<HashMap<_, _>>();
+
+        let registered = event_user::Entity::find()
+            .select_only()
+            .column(event_user::Column::EventId)
+            .column(event_user::Column::Round)
+            .column_as(event_user::Column::UserId.count(), "count")
+            .filter(event_user::Column::EventId.is_in(existing_ids.iter().copied()))
+            .group_by(event_user::Column::EventId)
+            .group_by(event_user::Column::Round)
+            .into_tuple::<(i32, i32, i64)>()
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|(event_id, round, count)| ((event_id, round), count))
+            .collect::<HashMap<_, _>>();
+
+        let errors = Self::registration_errors(&events, &existing, &registered, &options.columns);
+
+        if !errors.is_empty() {
+            transaction.rollback().await?;
+            return Ok(EventImportOutcome {
+                errors,
+                ..Default::default()
+            });
+        }
+
+        let mut outcome = EventImportOutcome::default();
+        let mut max_users = Vec::new();
+        let mut freed = Vec::new();
+
+        for imported in events {
+            let existed = existing.contains_key(&imported.name);
+
+            let event_id = match existing.get(&imported.name) {
+                Some(event) => {
+                    let mut event: event::ActiveModel = event.clone().into();
+                    event.room = Set(imported.room);
+                    event.zone = Set(imported.zone);
+                    event.floor = Set(imported.floor);
+                    if let Some(minimum_section) = imported.minimum_section {
+                        event.minimum_section = Set(minimum_section);
+                    }
+                    if let Some(round_span) = imported.round_span {
+                        event.round_span = Set(round_span);
+                    }
+
+                    outcome.updated += 1;
+                    event.update(&transaction).await?.id
+                }
+                None => {
+                    let event = event::ActiveModel {
+                        name: Set(imported.name),
+                        room: Set(imported.room),
+                        zone: Set(imported.zone),
+                        floor: Set(imported.floor),
+                        minimum_section: Set(imported.minimum_section.unwrap_or(1)),
+                        round_span: Set(imported.round_span.unwrap_or(1)),
+                        edition_id: Set(edition.id),
+                        ..Default::default()
+                    };
+
+                    outcome.created += 1;
+                    event.insert(&transaction).await?.id
+                }
+            };
+
+            if existed {
+                freed.extend(
+                    imported
+                        .max_users
+                        .iter()
+                        .filter(|(round, max_users)| {
+                            let current = current_max_users.get(&(event_id, *round));
+                            *max_users > current.copied().unwrap_or(0)
+                        })
+                        .map(|&(round, _)| EventSubscription { round, event_id }),
+                );
+            }
+
+            outcome.event_ids.push(event_id);
+
+            max_users.extend(imported.max_users.into_iter().map(|(round, max_users)| {
+                round_max_users::ActiveModel {
+                    round: Set(round),
+                    event_id: Set(event_id),
+                    max_users: Set(max_users),
+                }
+            }));
+        }
+
+        if !max_users.is_empty() {
+            round_max_users::Entity::insert_many(max_users)
+                .on_conflict(
+                    OnConflict::columns([
+                        round_max_users::Column::Round,
+                        round_max_users::Column::EventId,
+                    ])
+                    .update_column(round_max_users::Column::MaxUsers)
+                    .to_owned(),
+                )
+                .exec(&transaction)
+                .await?;
+        }
+
+        outcome.promotions =
+            Mutation::promote_waitlisted(&transaction, &freed, options.rules).await?;
+
+        if options.dry_run {
+            transaction.rollback().await?;
+        } else {
+            transaction.commit().await?;
+            outcome.applied = true;
+        }
+
+        Ok(outcome)
+    }
+
+    /// The changes the students registered to the existing events prevent: the capacities
+    /// lowered below them and the rounds the events last, as with the event RPCs
+    fn registration_errors(
+        events: &[ImportedEvent],
+        existing: &HashMap<String, event::Model>,
+        registered: &HashMap<(i32, i32), i64>,
+        columns: &EventColumns,
+    ) -> Vec<EventImportRowError> {
+        let mut errors = Vec::new();
+
+        for imported in events {
+            let Some(event) = existing.get(&imported.name) else {
+                continue;
+            };
+
+            let span_changed = imported
+                .round_span
+                .is_some_and(|round_span| round_span != event.round_span);
+            if span_changed && registered.keys().any(|&(event_id, _)| event_id == event.id) {
+                errors.push(EventImportRowError {
+                    row: imported.row,
+                    column: columns.round_span.clone().unwrap_or_default(),
+                    message: "Students are subscribed to the event, the rounds it lasts can't be \
+                              changed"
+                        .to_string(),
+                });
+            }
+
+            for &(round, max_users) in &imported.max_users {
+                let registered = registered.get(&(event.id, round)).copied().unwrap_or(0);
+                if i64::from(max_users) >= registered {
+                    continue;
+                }
+
+                let column = columns
+                    .rounds
+                    .iter()
+                    .find(|column| column.round == round)
+                    .map(|column| column.column.clone())
+                    .unwrap_or_default();
+
+                errors.push(EventImportRowError {
+                    row: imported.row,
+                    column,
+                    message: format!(
+                        "The capacity {max_users} is below the {registered} students registered \
+                         in round {round}, lower it with force from the capacities"
+                    ),
+                });
+            }
+        }
+
+        errors
+    }
+
+    fn read_sheet(workbook: Vec<u8>, sheet: Option<&str>) -> Result<Range<Data>, EventImportError> {
+        let mut workbook = Xlsx::new(Cursor::new(workbook))?;
+
+        let sheet = match sheet {
+            Some(sheet) => sheet.to_string(),
+            None => workbook
+                .sheet_names()
+                .first()
+                .cloned()
+                .ok_or(EventImportError::SheetNotFound("".to_string()))?,
+        };
+
+        if !workbook.sheet_names().contains(&sheet) {
+            return Err(EventImportError::SheetNotFound(sheet));
+        }
+
+        Ok(workbook.worksheet_range(&sheet)?)
+    }
+
+    fn column_indexes(
+        header: &[Data],
+        columns: &EventColumns,
+        rounds: &Rounds,
+    ) -> Result<ColumnIndexes, EventImportError> {
+        let index = |column: &str| {
+            header
+                .iter()
+                .position(|cell| cell_text(cell).to_lowercase() == column.trim().to_lowercase())
+                .ok_or_else(|| EventImportError::MissingColumn(column.to_string()))
+        };
+        let optional_index = |column: &Option<String>| column.as_deref().map(index).transpose();
+
+        let mut capacity_columns: Vec<CapacityColumn> = Vec::new();
+        for round_column in &columns.rounds {
+            if !rounds.contains(round_column.round) {
+                return Err(EventImportError::UnknownRound(round_column.round));
+            }
+            if capacity_columns
+                .iter()
+                .any(|column| column.rounds.contains(&round_column.round))
+            {
+                return Err(EventImportError::DuplicateRound(round_column.round));
+            }
+
+            let column_index = index(&round_column.column)?;
+            match capacity_columns
+                .iter_mut()
+                .find(|column| column.index == column_index)
+            {
+                Some(column) => column.rounds.push(round_column.round),
+                None => capacity_columns.push(CapacityColumn {
+                    column: round_column.column.clone(),
+                    index: column_index,
+                    rounds: vec![round_column.round],
+                }),
+            }
+        }
+
+        Ok(ColumnIndexes {
+            name: index(&columns.name)?,
+            room: index(&columns.room)?,
+            zone: index(&columns.zone)?,
+            floor: index(&columns.floor)?,
+            minimum_section: optional_index(&columns.minimum_section)?,
+            round_span: optional_index(&columns.round_span)?,
+            max_users: optional_index(&columns.max_users)?,
+            turn: optional_index(&columns.turn)?,
+            rounds: capacity_columns,
+        })
+    }
+
+    /// Reads the events from the rows following the header, skipping the empty ones
+    fn parse_rows(
+        range: &Range<Data>,
+        columns: &EventColumns,
+        rounds: &Rounds,
+        sections: Sections,
+    ) -> Result<(Vec<ImportedEvent>, Vec<EventImportRowError>), EventImportError> {
+        let mut rows = range.rows();
+
+        let header = rows
+            .next()
+            .ok_or_else(|| EventImportError::MissingColumn(columns.name.clone()))?;
+        let indexes = Self::column_indexes(header, columns, rounds)?;
+
+        let header_row = range.start().map(|(row, _)| row).unwrap_or(0) + 1;
+
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+        let mut rows_by_name: HashMap<String, u32> = HashMap::new();
+
+        for (row_number, row) in (header_row + 1..).zip(rows) {
+            if row.iter().all(|cell| cell_text(cell).is_empty()) {
+                continue;
+            }
+
+            let cell = |index: usize| row.get(index).unwrap_or(&Data::Empty);
+            let mut error = |column: &str, message: String| {
+                errors.push(EventImportRowError {
+                    row: row_number,
+                    column: column.to_string(),
+                    message,
+                })
+            };
+
+            let name = cell_text(cell(indexes.name));
+            if name.is_empty() {
+                error(&columns.name, "The name is missing".to_string());
+            } else if let Some(previous_row) = rows_by_name.insert(name.clone(), row_number) {
+                error(
+                    &columns.name,
+                    format!("The event {name:?} is already on row {previous_row}"),
+                );
+            }
+
+            let room = cell_text(cell(indexes.room));
+            if room.is_empty() {
+                error(&columns.room, "The room is missing".to_string());
+            }
+
+            let mut integer_in = |index: Option<usize>,
+                                  column: &Option<String>,
+                                  range: RangeInclusive<i32>| {
+                let (index, column) = index.zip(column.as_deref())?;
+                let value = cell(index);
+
+                match cell_integer(value).filter(|value| range.contains(value)) {
+                    Some(value) => Some(value),
+                    None => {
+                        let text = cell_text(value);
+                        let message = match range.end() {
+                            &i32::MAX => {
+                                format!("{text:?} is not an integer of at least {}", range.start())
+                            }
+                            max => format!(
+                                "{text:?} is not an integer between {} and {max}",
+                                range.start()
+                            ),
+                        };
+
+                        error(column, message);
+                        None
+                    }
+                }
+            };
+
+            let minimum_section = integer_in(
+                indexes.minimum_section,
+                &columns.minimum_section,
+                1..=sections.max,
+            );
+            let round_span = integer_in(indexes.round_span, &columns.round_span, 1..=i32::MAX);
+            let capacity = indexes
+                .max_users
+                .filter(|index| !cell_text(cell(*index)).is_empty())
+                .and_then(|index| integer_in(Some(index), &columns.max_users, 0..=i32::MAX));
+
+            let turn = indexes
+                .turn
+                .filter(|index| !cell_text(cell(*index)).is_empty())
+                .and_then(|index| integer_in(Some(index), &columns.turn, 1..=i32::MAX));
+
+            if let Some(round_span) = round_span.filter(|span| !rounds.fits_in_a_day(*span)) {
+                error(
+                    columns.round_span.as_deref().unwrap_or_default(),
+                    format!("An event can't last {round_span} rounds, no day has that many"),
+                );
+            }
+
+            let mut max_users = Vec::new();
+            for capacity_column in &indexes.rounds {
+                let value = cell(capacity_column.index);
+                let text = cell_text(value);
+                let column_rounds = &capacity_column.rounds;
+
+                let mut marked = |max_users: &mut Vec<(i32, i32)>, turn: Option<usize>| {
+                    let Some(capacity) = capacity else {
+                        error(
+                            &capacity_column.column,
+                            "Marked with an X, but the row has no capacity".to_string(),
+                        );
+                        return;
+                    };
+
+                    max_users.extend(column_rounds.iter().enumerate().map(|(i, &round)| {
+                        let runs = turn.is_none_or(|turn| turn == i + 1);
+                        (round, if runs { capacity } else { 0 })
+                    }));
+                };
+
+                if text.is_empty() {
+                    max_users.extend(column_rounds.iter().map(|&round| (round, 0)));
+                } else if text.eq_ignore_ascii_case(&"x".repeat(column_rounds.len())) {
+                    marked(&mut max_users, None);
+                } else if text.eq_ignore_ascii_case("x") {
+                    match turn.map(|turn| turn as usize) {
+                        // The legacy workbooks leave the event out of the rounds of the column
+                        None => max_users.extend(column_rounds.iter().map(|&round| (round, 0))),
+                        Some(turn) if turn <= column_rounds.len() => {
+                            marked(&mut max_users, Some(turn))
+                        }
+                        Some(turn) => error(
+                            columns.turn.as_deref().unwrap_or_default(),
+                            format!(
+                                "Turn {turn} is not one of the {} rounds of {:?}",
+                                column_rounds.len(),
+                                capacity_column.column
+                            ),
+                        ),
+                    }
+                } else {
+                    match cell_integer(value).filter(|value| *value >= 0) {
+                        Some(capacity) => {
+                            max_users.extend(column_rounds.iter().map(|&round| (round, capacity)))
+                        }
+                        None => error(
+                            &capacity_column.column,
+                            format!("{text:?} is neither a capacity nor an X"),
+                        ),
+                    }
+                }
+            }
+
+            if errors.last().is_some_and(|e| e.row == row_number) {
+                continue;
+            }
+
+            events.push(ImportedEvent {
+                row: row_number,
+                name,
+                room,
+                zone: cell_text(cell(indexes.zone)),
+                floor: cell_text(cell(indexes.floor)),
+                minimum_section,
+                round_span,
+                max_users,
+            });
+        }
+
+        Ok((events, errors))
+    }
+}
+
+fn cell_text(cell: &Data) -> String {
+    cell.to_string().trim().to_string()
+}
+
+/// The value of a cell holding an integer, either as a number or as text
+fn cell_integer(cell: &Data) -> Option<i32> {
+    match cell {
+        Data::Int(value) => i32::try_from(*value).ok(),
+        Data::Float(value) if value.fract() == 0.0 => i32::try_from(*value as i64).ok(),
+        Data::String(value) => value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::entity::round;
+    use sea_orm::prelude::{Date, Time};
+
+    /// Two days of two rounds, numbered from 1
+    fn rounds() -> Rounds {
+        let round = |id: i32, day: u32, hour: u32| round::Model {
+            id,
+            day: Date::from_ymd_opt(2024, 2, day).unwrap(),
+            start_time: Time::from_hms_opt(hour, 30, 0).unwrap(),
+            end_time: Time::from_hms_opt(hour + 2, 30, 0).unwrap(),
+            label: format!("Round {id}"),
+            edition_id: 1,
+        };
+
+        vec![
+            round(1, 22, 8),
+            round(2, 22, 10),
+            round(3, 23, 8),
+            round(4, 23, 10),
+        ]
+        .into()
+    }
+
+    fn legacy_columns() -> EventColumns {
+        let round_column = |round: i32, column: &str| RoundColumn {
+            round,
+            column: column.to_string(),
+        };
+
+        EventColumns {
+            name: "Attività".to_string(),
+            room: "Aula".to_string(),
+            zone: "ZONA".to_string(),
+            floor: "PIANO".to_string(),
+            max_users: Some("n.Alunni".to_string()),
+            turn: Some("turno?".to_string()),
+            rounds: vec![
+                round_column(1, "1°giorno"),
+                round_column(2, "1°giorno"),
+                round_column(3, "2°giorno"),
+                round_column(4, "2°giorno"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn columns() -> EventColumns {
+        EventColumns {
+            name: "name".to_string(),
+            room: "room".to_string(),
+            zone: "zone".to_string(),
+            floor: "floor".to_string(),
+            minimum_section: Some("minimum_section".to_string()),
+            round_span: Some("round_span".to_string()),
+            max_users: Some("max_users".to_string()),
+            turn: None,
+            rounds: vec![
+                RoundColumn {
+                    round: 1,
+                    column: "first".to_string(),
+                },
+                RoundColumn {
+                    round: 2,
+                    column: "second".to_string(),
+                },
+            ],
+        }
+    }
+
+    /// A sheet with the columns of `columns` and the given rows, the empty cells are left empty
+    fn sheet(rows: &[[&str; 9]]) -> Range<Data> {
+        let header = [
+            "Name",
+            "Room",
+            "Zone",
+            "Floor",
+            "Minimum_Section",
+            "Round_Span",
+            "Max_Users",
+            "First",
+            "Second",
+        ];
+
+        let mut range = Range::new((0, 0), (rows.len() as u32, header.len() as u32 - 1));
+        for (row, cells) in (0..).zip(std::iter::once(&header).chain(rows)) {
+            for (column, cell) in (0..).zip(cells) {
+                if !cell.is_empty() {
+                    range.set_value((row, column), Data::String(cell.to_string()));
+                }
+            }
+        }
+
+        range
+    }
+
+    fn parse(rows: &[[&str; 9]]) -> (Vec<ImportedEvent>, Vec<EventImportRowError>) {
+        EventImport::parse_rows(&sheet(rows), &columns(), &rounds(), Sections::default()).unwrap()
+    }
+
+    fn row_error(row: u32, column: &str) -> impl Fn(&EventImportRowError) -> bool + '_ {
+        move |error| error.row == row && error.column == column
+    }
+
+    #[test]
+    fn reads_the_events() {
+        let (events, errors) = parse(&[
+            ["Chess", "A1", "North", "1", "2", "1", "20", "X", ""],
+            ["Theatre", "B2", "South", "0", "1", "2", "", "15", ""],
+        ]);
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0].name, "Chess");
+        assert_eq!(events[0].room, "A1");
+        assert_eq!(events[0].minimum_section, Some(2));
+        assert_eq!(events[0].round_span, Some(1));
+        assert_eq!(events[0].max_users, vec![(1, 20), (2, 0)]);
+
+        assert_eq!(events[1].round_span, Some(2));
+        assert_eq!(events[1].max_users, vec![(1, 15), (2, 0)]);
+    }
+
+    #[test]
+    fn leaves_out_the_columns_not_mapped() {
+        let columns = EventColumns {
+            minimum_section: None,
+            round_span: None,
+            max_users: None,
+            ..columns()
+        };
+        let range = sheet(&[["Chess", "A1", "", "", "one", "", "", "10", ""]]);
+
+        let (events, errors) =
+            EventImport::parse_rows(&range, &columns, &rounds(), Sections::default()).unwrap();
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(events[0].minimum_section, None);
+        assert_eq!(events[0].round_span, None);
+        assert_eq!(events[0].max_users, vec![(1, 10), (2, 0)]);
+    }
+
+    #[test]
+    fn an_x_needs_the_capacity_of_the_row() {
+        let (events, errors) = parse(&[["Theatre", "A1", "", "", "1", "1", "", "15", "X"]]);
+
+        assert!(events.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(row_error(2, "second")(&errors[0]));
+    }
+
+    #[test]
+    fn reports_the_malformed_rows() {
+        let (events, errors) = parse(&[
+            ["", "A1", "", "", "1", "1", "20", "X", "X"],
+            ["Chess", "A1", "", "", "one", "1", "20", "X", "X"],
+            ["Theatre", "A1", "", "", "1", "0", "20", "X", "X"],
+            ["Dance", "A1", "", "", "1", "1", "-5", "", ""],
+            ["Music", "A1", "", "", "1", "1", "20", "Y", "-1"],
+            ["Poetry", "A1", "", "", "1", "1", "20", "X", "X"],
+            ["Cinema", "A1", "", "", "6", "1", "20", "X", "X"],
+            ["Comics", "A1", "", "", "1", "3", "20", "X", "X"],
+            ["Sport", "", "", "", "1", "1", "20", "X", "X"],
+        ]);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "Poetry");
+
+        assert!(errors.iter().any(row_error(2, "name")));
+        assert!(errors.iter().any(row_error(3, "minimum_section")));
+        assert!(errors.iter().any(row_error(4, "round_span")));
+        assert!(errors.iter().any(row_error(5, "max_users")));
+        assert!(errors.iter().any(row_error(6, "first")));
+        assert!(errors.iter().any(row_error(6, "second")));
+        assert!(!errors.iter().any(|error| error.row == 7));
+        // Above the sections of the school
+        assert!(errors.iter().any(row_error(8, "minimum_section")));
+        // Longer than the two rounds of a day
+        assert!(errors.iter().any(row_error(9, "round_span")));
+        assert!(errors.iter().any(row_error(10, "room")));
+    }
+
+    #[test]
+    fn reports_the_repeated_names() {
+        let (events, errors) = parse(&[
+            ["Chess", "A1", "", "", "1", "1", "", "10", ""],
+            ["Theatre", "A1", "", "", "1", "1", "", "10", ""],
+            ["Chess", "A1", "", "", "1", "1", "", "", "10"],
+        ]);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            errors,
+            vec![EventImportRowError {
+                row: 4,
+                column: "name".to_string(),
+                message: "The event \"Chess\" is already on row 2".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn skips_the_empty_rows() {
+        let (events, errors) = parse(&[
+            ["", "", "", "", "", "", "", "", ""],
+            ["Chess", "A1", "", "", "1", "1", "", "10", ""],
+            ["", "", "", "", "", "", "", "", ""],
+        ]);
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn keeps_the_registrations_valid() {
+        let (events, errors) = parse(&[
+            ["Chess", "A1", "", "", "1", "2", "", "10", ""],
+            ["Theatre", "A1", "", "", "1", "2", "", "10", ""],
+            ["Dance", "A1", "", "", "1", "1", "", "2", ""],
+        ]);
+        assert_eq!(errors, vec![]);
+
+        let event = |id: i32, name: &str| event::Model {
+            id,
+            name: name.to_string(),
+            room: "A1".to_string(),
+            zone: "".to_string(),
+            floor: "".to_string(),
+            minimum_section: 1,
+            round_span: 1,
+            edition_id: 1,
+        };
+        let existing = [event(1, "Chess"), event(2, "Theatre"), event(3, "Dance")]
+            .into_iter()
+            .map(|event| (event.name.clone(), event))
+            .collect();
+        let registered = HashMap::from([((1, 1), 3), ((3, 1), 5)]);
+
+        let errors = EventImport::registration_errors(&events, &existing, &registered, &columns());
+
+        // Theatre has no students, its span can change
+        assert_eq!(errors.len(), 2);
+        assert!(row_error(2, "round_span")(&errors[0]));
+        assert!(row_error(4, "first")(&errors[1]));
+    }
+
+    #[test]
+    fn rejects_the_unknown_columns_and_rounds() {
+        let mut columns = columns();
+        columns.room = "classroom".to_string();
+        assert!(matches!(
+            EventImport::parse_rows(&sheet(&[]), &columns, &rounds(), Sections::default()),
+            Err(EventImportError::MissingColumn(column)) if column == "classroom"
+        ));
+
+        let mut columns = self::columns();
+        columns.rounds[1].round = 5;
+        assert!(matches!(
+            EventImport::parse_rows(&sheet(&[]), &columns, &rounds(), Sections::default()),
+            Err(EventImportError::UnknownRound(5))
+        ));
+
+        let mut columns = self::columns();
+        columns.rounds[1].round = 1;
+        assert!(matches!(
+            EventImport::parse_rows(&sheet(&[]), &columns, &rounds(), Sections::default()),
+            Err(EventImportError::DuplicateRound(1))
+        ));
+    }
+
+    #[test]
+    fn reads_the_legacy_workbook() {
+        let workbook = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../seed/xlsx/ATTIVITÀ FORUM DEFINITIVE.xlsx"
+        ))
+        .unwrap();
+        let range = EventImport::read_sheet(workbook, Some("Foglio1")).unwrap();
+
+        let (events, errors) =
+            EventImport::parse_rows(&range, &legacy_columns(), &rounds(), Sections::default())
+                .unwrap();
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(events.len(), 62);
+
+        let max_users = |name: &str| {
+            events
+                .iter()
+                .find(|event| event.name == name)
+                .unwrap()
+                .max_users
+                .clone()
+        };
+
+        // XX on the second day only
+        assert_eq!(
+            max_users("Lezione su sfruttamento ambientale ed animale"),
+            vec![(1, 0), (2, 0), (3, 24), (4, 24)]
+        );
+        // X on both days, in the second turn
+        assert_eq!(
+            max_users("Difesa personale e Muay Thai"),
+            vec![(1, 0), (2, 15), (3, 0), (4, 15)]
+        );
+        // X on the second day, in the first turn
+        assert_eq!(
+            max_users("Lezione Machine learning"),
+            vec![(1, 0), (2, 0), (3, 30), (4, 0)]
+        );
+        // X without a turn
+        assert_eq!(
+            max_users("Lezione di produzione musicale"),
+            vec![(1, 0), (2, 0), (3, 0), (4, 0)]
+        );
+    }
+}
@@ -1,7 +1,23 @@
+mod allocation;
+mod capacity;
+mod edition;
+mod event_import;
 pub mod mutation;
 mod query;
+mod registration_window;
+mod roster_import;
+mod rounds;
+mod users;
 
+pub use allocation::*;
+pub use capacity::*;
+pub use edition::*;
+pub use event_import::*;
 pub use mutation::*;
 pub use query::*;
+pub use registration_window::*;
+pub use roster_import::*;
+pub use rounds::*;
+pub use users::*;
 
 pub use sea_orm;
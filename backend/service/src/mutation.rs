@@ -1,107 +1,1275 @@
-use ::entity::{event_user, user};
+use crate::edition::in_active_edition;
+use crate::Rounds;
+use ::entity::{
+    edition, event, event_preference, event_user, event_waitlist, round_max_users,
+    sea_orm_active_enums::UserRole, user,
+};
 use chrono::Utc;
 use sea_orm::*;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
 #[derive(Debug)]
 pub struct UserToCreate {
-    pub name: String,
+    /// The name of an existing user is kept when none is given
+    pub name: Option<String>,
     pub email: String,
     pub section: i32,
     pub class: String,
-    pub admin: bool,
+    pub role: UserRole,
+}
+
+/// The rules every student's subscriptions must follow
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SubscriptionRules {
+    /// Whether the same event can be picked in more than one round, the rounds booked by a
+    /// multi-round event count as a single pick
+    pub allow_repeated_events: bool,
+}
+
+/// The choice of an event for a round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventSubscription {
+    pub round: i32,
+    pub event_id: i32,
+}
+
+/// The events a student ranked for a round, from the most to the least wanted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundPreferences {
+    pub round: i32,
+    pub event_ids: Vec<i32>,
+}
+
+/// A student moved from the waitlist of an event to the event itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitlistPromotion {
+    pub user_id: i32,
+    pub round: i32,
+    pub event_id: i32,
+    /// The event the student was subscribed to in the round before being promoted
+    pub previous_event_id: Option<i32>,
+}
+
+/// The subscriptions changed by `Mutation::subscribe_to_events`
+#[derive(Debug, Default)]
+pub struct SubscriptionChanges {
+    pub added: Vec<EventSubscription>,
+    pub removed: Vec<EventSubscription>,
+    /// The waitlisted students that took the seats freed by the removed subscriptions
+    pub promotions: Vec<WaitlistPromotion>,
+}
+
+impl SubscriptionChanges {
+    /// The events whose subscriber count changed
+    pub fn affected_event_ids(&self) -> Vec<i32> {
+        let mut event_ids = self
+            .added
+            .iter()
+            .chain(self.removed.iter())
+            .map(|s| s.event_id)
+            .chain(
+                self.promotions
+                    .iter()
+                    .flat_map(|p| std::iter::once(p.event_id).chain(p.previous_event_id)),
+            )
+            .collect::<Vec<_>>();
+
+        event_ids.sort_unstable();
+        event_ids.dedup();
+
+        event_ids
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubscribeError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("Invalid rounds: {0}")]
+    InvalidRounds(String),
+
+    #[error("User {0} not found")]
+    UserNotFound(i32),
+
+    #[error("The section of the user is too low for the following events: {0:?}")]
+    NotEligible(Vec<i32>),
+
+    #[error("The following events are full: {0:?}")]
+    EventsFull(Vec<EventSubscription>),
+
+    #[error("The following events can't be picked in more than one round: {0:?}")]
+    RepeatedEvents(Vec<i32>),
+
+    #[error("The following multi-round events start too late to fit in the rounds: {0:?}")]
+    RoundSpanPastLastRound(Vec<i32>),
+
+    #[error("The following subscriptions overlap with the rounds of multi-round events: {0:?}")]
+    OverlappingRounds(Vec<EventSubscription>),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WaitlistError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("Round {0} doesn't exist")]
+    InvalidRound(i32),
+
+    #[error("User {0} not found")]
+    UserNotFound(i32),
+
+    #[error("The section of the user is too low for the event")]
+    NotEligible,
+
+    #[error("The event doesn't run in this round")]
+    NotRunning,
+
+    #[error("The event still has seats left, subscribe to it instead")]
+    NotFull,
+
+    #[error("The user is already subscribed to the event in this round")]
+    AlreadySubscribed,
+
+    #[error("The user is already subscribed to the event in another round")]
+    RepeatedEvent,
+
+    #[error("Multi-round events have no waitlist")]
+    MultiRound,
+
+    #[error("The user is in a multi-round event in this round, it must leave it first")]
+    InMultiRoundEvent,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PreferencesError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("{0}")]
+    InvalidPreferences(String),
+
+    #[error("User {0} not found")]
+    UserNotFound(i32),
+
+    #[error("The section of the user is too low for the following events: {0:?}")]
+    NotEligible(Vec<i32>),
+
+    #[error("The following events don't run in their round: {0:?}")]
+    NotRunning(Vec<EventSubscription>),
+}
+
+/// The highest section of a school when it isn't configured
+pub const DEFAULT_MAX_SECTION: i32 = 5;
+
+/// The sections of the school, numbered from 1, the minimum section of an event and the
+/// section of a student must be one of them
+#[derive(Debug, Clone, Copy)]
+pub struct Sections {
+    pub max: i32,
+}
+
+impl Sections {
+    pub fn contains(&self, section: i32) -> bool {
+        (1..=self.max).contains(&section)
+    }
+}
+
+impl Default for Sections {
+    fn default() -> Self {
+        Self {
+            max: DEFAULT_MAX_SECTION,
+        }
+    }
+}
+
+/// The fields of an event set by an admin
+#[derive(Debug, Clone)]
+pub struct EventToSave {
+    pub name: String,
+    pub room: String,
+    pub zone: String,
+    pub floor: String,
+    pub minimum_section: i32,
+    pub round_span: i32,
+}
+
+/// What to do when deleting an event students are subscribed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventDeletePolicy {
+    /// Keep the event, failing with `EventError::HasSubscriptions`
+    Refuse,
+    /// Delete the event along with its subscriptions, waitlists and preferences
+    Cascade,
+}
+
+/// A subscription removed along with its event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CancelledSubscription {
+    pub user_id: i32,
+    pub round: i32,
+}
+
+#[derive(Debug)]
+pub struct DeletedEvent {
+    pub event: event::Model,
+    pub cancelled: Vec<CancelledSubscription>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EventError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("{0}")]
+    InvalidEvent(String),
+
+    #[error("Event {0} not found")]
+    NotFound(i32),
+
+    #[error("An event named {0:?} already exists")]
+    DuplicateName(String),
+
+    #[error("There is no active edition to add the event to")]
+    NoActiveEdition,
+
+    #[error("{0} students are subscribed to the event")]
+    HasSubscriptions(u64),
+
+    #[error("Students are subscribed to the event, the rounds it lasts can't be changed")]
+    RoundSpanInUse,
 }
 
 pub struct Mutation;
 
 impl Mutation {
-    /// Add a user to the database
+    /// Add a user to the database, if a user with the same email already exists
+    /// its name is updated when one is given and the existing user is returned
     pub async fn add_user(db: &DbConn, user: UserToCreate) -> Result<user::Model, DbErr> {
+        // Updating the email to itself leaves the user unchanged, but still returns it
+        let updated_column = if user.name.is_some() {
+            user::Column::Name
+        } else {
+            user::Column::Email
+        };
+
         let user = user::ActiveModel {
             id: Default::default(),
-            name: Set(Some(user.name)),
+            name: Set(user.name),
             email: Set(user.email),
             interactive_done: Default::default(),
             section: Set(user.section),
             class: Set(Some(user.class)),
-            admin: Set(user.admin),
+            role: Set(user.role),
+            active: Default::default(),
         };
 
-        user.insert(db).await
+        user::Entity::insert(user)
+            .on_conflict(
+                sea_query::OnConflict::column(user::Column::Email)
+                    .update_column(updated_column)
+                    .to_owned(),
+            )
+            .exec_with_returning(db)
+            .await
     }
 
-    /// Subscribe to events for a user
+    /// Subscribe to events for a user, with one event for every round.
+    ///
+    /// A multi-round event picked in a round also books the following rounds it spans, they can
+    /// be either picked with the same event or left out.
+    ///
+    /// Only the changed rounds are touched: the subscriptions that are kept retain their
+    /// attendance, the removed ones are deleted and the new ones are inserted, all in the same
+    /// transaction. The capacity of every new event is checked locking its `round_max_users`
+    /// row so that concurrent subscriptions can't oversubscribe it.
     pub async fn subscribe_to_events(
         db: &DbConn,
         user_id: i32,
-        event_ids: &Vec<i32>,
-    ) -> Result<(), DbErr> {
-        let mut events = Vec::with_capacity(event_ids.len());
+        subscriptions: &[EventSubscription],
+        rules: SubscriptionRules,
+    ) -> Result<SubscriptionChanges, SubscribeError> {
+        let transaction = db.begin().await?;
+
+        let rounds = Rounds::load(&transaction).await?;
+
+        let round_spans = event::Entity::find()
+            .filter(event::Column::Id.is_in(subscriptions.iter().map(|s| s.event_id)))
+            .filter(event::Column::RoundSpan.gt(1))
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|event| (event.id, event.round_span))
+            .collect::<HashMap<_, _>>();
 
-        // Cleanup older event subscriptions for the user
-        Self::remove_old_subscriptions_for_user(db, user_id).await?;
+        let subscriptions = Self::book_spanned_rounds(subscriptions, &round_spans, &rounds, rules)?;
+        let subscriptions = subscriptions.as_slice();
 
-        for event_id in event_ids {
-            let event_user = event_user::ActiveModel {
-                user_id: Set(user_id),
-                joined_at: Default::default(),
-                left_at: Default::default(),
-                event_id: Set(*event_id),
-                round: Default::default(),
-            };
+        Self::validate_subscription_rounds(subscriptions, &rounds)?;
 
-            events.push(event_user);
+        let user = user::Entity::find_by_id(user_id)
+            .one(&transaction)
+            .await?
+            .ok_or(SubscribeError::UserNotFound(user_id))?;
+
+        // Lock the current subscriptions, so that concurrent requests for the same user
+        // are applied one after the other. The attendance of past editions is left untouched.
+        let current: Vec<EventSubscription> = event_user::Entity::find()
+            .filter(event_user::Column::UserId.eq(user_id))
+            .filter(event_user::Column::Round.is_in(rounds.ids()))
+            .lock_exclusive()
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|event_user| EventSubscription {
+                round: event_user.round,
+                event_id: event_user.event_id,
+            })
+            .collect();
+
+        let mut changes = SubscriptionChanges {
+            added: subscriptions
+                .iter()
+                .filter(|s| !current.contains(s))
+                .copied()
+                .collect(),
+            removed: current
+                .iter()
+                .filter(|s| !subscriptions.contains(s))
+                .copied()
+                .collect(),
+            promotions: Vec::new(),
+        };
+
+        let not_eligible_events =
+            Self::not_eligible_events_for_subscriptions(&transaction, &user, &changes.added)
+                .await?;
+
+        if !not_eligible_events.is_empty() {
+            transaction.rollback().await?;
+            return Err(SubscribeError::NotEligible(not_eligible_events));
         }
 
-        event_user::Entity::insert_many(events)
-            .exec(db)
-            .await
-            .map_err(DbErr::from)?;
+        if !changes.removed.is_empty() {
+            let removed_rounds = changes.removed.iter().map(|s| s.round).collect::<Vec<_>>();
+
+            event_user::Entity::delete_many()
+                .filter(event_user::Column::UserId.eq(user_id))
+                .filter(event_user::Column::Round.is_in(removed_rounds))
+                .exec(&transaction)
+                .await?;
+        }
+
+        if !changes.added.is_empty() {
+            let full_events =
+                Self::full_events_for_subscriptions(&transaction, &changes.added).await?;
+
+            if !full_events.is_empty() {
+                transaction.rollback().await?;
+                return Err(SubscribeError::EventsFull(full_events));
+            }
+
+            let events = changes
+                .added
+                .iter()
+                .map(|subscription| event_user::ActiveModel {
+                    user_id: Set(user_id),
+                    joined_at: Default::default(),
+                    left_at: Default::default(),
+                    event_id: Set(subscription.event_id),
+                    round: Set(subscription.round),
+                })
+                .collect::<Vec<_>>();
+
+            event_user::Entity::insert_many(events)
+                .exec(&transaction)
+                .await?;
+
+            // The user doesn't have to wait anymore for the events it got
+            let mut condition = Condition::any();
+            for subscription in &changes.added {
+                condition = condition.add(
+                    event_waitlist::Column::Round
+                        .eq(subscription.round)
+                        .and(event_waitlist::Column::EventId.eq(subscription.event_id)),
+                );
+            }
+
+            event_waitlist::Entity::delete_many()
+                .filter(event_waitlist::Column::UserId.eq(user_id))
+                .filter(condition)
+                .exec(&transaction)
+                .await?;
+        }
+
+        changes.promotions =
+            Self::promote_waitlisted(&transaction, &changes.removed, rules).await?;
+
+        transaction.commit().await?;
+
+        Ok(changes)
+    }
+
+    /// Replace the ranked preferences of a user, every round must rank at least one event
+    /// and an event can be ranked only once in a round.
+    ///
+    /// The seats are only assigned later, by `Allocation::allocate_preferences`.
+    pub async fn set_preferences(
+        db: &DbConn,
+        user_id: i32,
+        preferences: &[RoundPreferences],
+    ) -> Result<(), PreferencesError> {
+        let transaction = db.begin().await?;
+
+        let rounds = Rounds::load(&transaction).await?;
+        Self::validate_preferences(preferences, &rounds)?;
+
+        let user = user::Entity::find_by_id(user_id)
+            .one(&transaction)
+            .await?
+            .ok_or(PreferencesError::UserNotFound(user_id))?;
+
+        let subscriptions = preferences
+            .iter()
+            .flat_map(|p| {
+                p.event_ids.iter().map(|&event_id| EventSubscription {
+                    round: p.round,
+                    event_id,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let not_eligible_events =
+            Self::not_eligible_events_for_subscriptions(&transaction, &user, &subscriptions)
+                .await?;
+
+        if !not_eligible_events.is_empty() {
+            transaction.rollback().await?;
+            return Err(PreferencesError::NotEligible(not_eligible_events));
+        }
+
+        let running = round_max_users::Entity::find()
+            .filter(round_max_users::Column::MaxUsers.gt(0))
+            .filter(round_max_users::Column::Round.is_in(rounds.ids()))
+            .all(&transaction)
+            .await?;
+
+        let not_running = subscriptions
+            .iter()
+            .filter(|s| {
+                !running
+                    .iter()
+                    .any(|m| m.round == s.round && m.event_id == s.event_id)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        if !not_running.is_empty() {
+            transaction.rollback().await?;
+            return Err(PreferencesError::NotRunning(not_running));
+        }
+
+        event_preference::Entity::delete_many()
+            .filter(event_preference::Column::UserId.eq(user_id))
+            .filter(event_preference::Column::Round.is_in(rounds.ids()))
+            .exec(&transaction)
+            .await?;
+
+        let rows = preferences
+            .iter()
+            .flat_map(|p| {
+                p.event_ids.iter().enumerate().map(|(index, &event_id)| {
+                    event_preference::ActiveModel {
+                        user_id: Set(user_id),
+                        round: Set(p.round),
+                        rank: Set(index as i32 + 1),
+                        event_id: Set(event_id),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        event_preference::Entity::insert_many(rows)
+            .exec(&transaction)
+            .await?;
+
+        transaction.commit().await?;
 
         Ok(())
     }
 
-    /// Remove all event subscriptions for a user
-    async fn remove_old_subscriptions_for_user(db: &DbConn, user_id: i32) -> Result<(), DbErr> {
-        event_user::Entity::delete_many()
-            .filter(event_user::Column::UserId.eq(user_id))
+    /// Put the user on the waitlist of a full event for a round, replacing the event it was
+    /// waiting for in that round. Returns the position of the user on the waitlist (from 1).
+    pub async fn join_waitlist(
+        db: &DbConn,
+        user_id: i32,
+        subscription: EventSubscription,
+        rules: SubscriptionRules,
+    ) -> Result<u64, WaitlistError> {
+        let transaction = db.begin().await?;
+
+        if !Rounds::load(&transaction)
+            .await?
+            .contains(subscription.round)
+        {
+            return Err(WaitlistError::InvalidRound(subscription.round));
+        }
+
+        let user = user::Entity::find_by_id(user_id)
+            .one(&transaction)
+            .await?
+            .ok_or(WaitlistError::UserNotFound(user_id))?;
+
+        let not_eligible_events =
+            Self::not_eligible_events_for_subscriptions(&transaction, &user, &[subscription])
+                .await?;
+
+        if !not_eligible_events.is_empty() {
+            return Err(WaitlistError::NotEligible);
+        }
+
+        let current = event_user::Entity::find_by_id((user_id, subscription.round))
+            .one(&transaction)
+            .await?;
+
+        if current
+            .as_ref()
+            .is_some_and(|current| current.event_id == subscription.event_id)
+        {
+            return Err(WaitlistError::AlreadySubscribed);
+        }
+
+        let is_multi_round = event::Entity::find_by_id(subscription.event_id)
+            .one(&transaction)
+            .await?
+            .is_some_and(|event| event.round_span > 1);
+
+        if is_multi_round {
+            return Err(WaitlistError::MultiRound);
+        }
+
+        if let Some(current) = &current {
+            if Self::is_multi_round_event(&transaction, current.event_id).await? {
+                return Err(WaitlistError::InMultiRoundEvent);
+            }
+        }
+
+        if !rules.allow_repeated_events
+            && Self::is_subscribed_in_another_round(&transaction, user_id, subscription).await?
+        {
+            return Err(WaitlistError::RepeatedEvent);
+        }
+
+        let max_users =
+            round_max_users::Entity::find_by_id((subscription.round, subscription.event_id))
+                .lock_exclusive()
+                .one(&transaction)
+                .await?
+                .map(|m| m.max_users)
+                .unwrap_or(0);
+
+        if max_users <= 0 {
+            return Err(WaitlistError::NotRunning);
+        }
+
+        if Self::count_subscribers(&transaction, subscription).await? < max_users as u64 {
+            return Err(WaitlistError::NotFull);
+        }
+
+        let entry = event_waitlist::ActiveModel {
+            user_id: Set(user_id),
+            round: Set(subscription.round),
+            event_id: Set(subscription.event_id),
+            created_at: Set(Utc::now().naive_utc()),
+        };
+
+        let entry = event_waitlist::Entity::insert(entry)
+            .on_conflict(
+                sea_query::OnConflict::columns([
+                    event_waitlist::Column::UserId,
+                    event_waitlist::Column::Round,
+                ])
+                .update_columns([
+                    event_waitlist::Column::EventId,
+                    event_waitlist::Column::CreatedAt,
+                ])
+                .to_owned(),
+            )
+            .exec_with_returning(&transaction)
+            .await?;
+
+        let position = event_waitlist::Entity::find()
+            .filter(event_waitlist::Column::EventId.eq(entry.event_id))
+            .filter(event_waitlist::Column::Round.eq(entry.round))
+            .filter(event_waitlist::Column::CreatedAt.lte(entry.created_at))
+            .count(&transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(position)
+    }
+
+    /// Remove the user from the waitlist it is on for a round
+    pub async fn leave_waitlist(db: &DbConn, user_id: i32, round: i32) -> Result<(), DbErr> {
+        event_waitlist::Entity::delete_by_id((user_id, round))
             .exec(db)
-            .await
-            .map_err(DbErr::from)?;
+            .await?;
 
         Ok(())
     }
 
-    /// Join an event (set the joined_at field to now)
-    pub async fn join_event(db: &DbConn, user_id: i32, event_id: i32) -> Result<(), DbErr> {
-        let event_user: Option<event_user::Model> = event_user::Entity::find()
-            .filter(event_user::Column::UserId.eq(user_id))
+    /// Add an event to the active edition, it doesn't run in any round until its capacity is set
+    pub async fn create_event(
+        db: &DbConn,
+        event: EventToSave,
+        sections: Sections,
+    ) -> Result<event::Model, EventError> {
+        let transaction = db.begin().await?;
+
+        let edition = edition::Entity::find()
+            .filter(edition::Column::Active.eq(true))
+            .one(&transaction)
+            .await?
+            .ok_or(EventError::NoActiveEdition)?;
+
+        let event = Self::validate_event(&transaction, None, event, sections).await?;
+
+        let event = event::ActiveModel {
+            name: Set(event.name),
+            room: Set(event.room),
+            zone: Set(event.zone),
+            floor: Set(event.floor),
+            minimum_section: Set(event.minimum_section),
+            round_span: Set(event.round_span),
+            edition_id: Set(edition.id),
+            ..Default::default()
+        }
+        .insert(&transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(event)
+    }
+
+    /// Change an event of the active edition, the students already subscribed to it keep their
+    /// seats even if their section is now too low
+    pub async fn update_event(
+        db: &DbConn,
+        event_id: i32,
+        event: EventToSave,
+        sections: Sections,
+    ) -> Result<event::Model, EventError> {
+        let transaction = db.begin().await?;
+
+        let current = Self::find_active_event(&transaction, event_id).await?;
+
+        let event = Self::validate_event(&transaction, Some(event_id), event, sections).await?;
+
+        if event.round_span != current.round_span
+            && Self::count_event_subscribers(&transaction, event_id).await? > 0
+        {
+            transaction.rollback().await?;
+            return Err(EventError::RoundSpanInUse);
+        }
+
+        let mut current: event::ActiveModel = current.into();
+        current.name = Set(event.name);
+        current.room = Set(event.room);
+        current.zone = Set(event.zone);
+        current.floor = Set(event.floor);
+        current.minimum_section = Set(event.minimum_section);
+        current.round_span = Set(event.round_span);
+        let event = current.update(&transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok(event)
+    }
+
+    /// Delete an event of the active edition, along with the subscriptions to it when the policy
+    /// allows it
+    pub async fn delete_event(
+        db: &DbConn,
+        event_id: i32,
+        policy: EventDeletePolicy,
+    ) -> Result<DeletedEvent, EventError> {
+        let transaction = db.begin().await?;
+
+        let event = Self::find_active_event(&transaction, event_id).await?;
+
+        let cancelled = event_user::Entity::find()
             .filter(event_user::Column::EventId.eq(event_id))
+            .order_by_asc(event_user::Column::UserId)
+            .order_by_asc(event_user::Column::Round)
+            .lock_exclusive()
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|event_user| CancelledSubscription {
+                user_id: event_user.user_id,
+                round: event_user.round,
+            })
+            .collect::<Vec<_>>();
+
+        if !cancelled.is_empty() && policy == EventDeletePolicy::Refuse {
+            transaction.rollback().await?;
+            return Err(EventError::HasSubscriptions(cancelled.len() as u64));
+        }
+
+        // The subscriptions, waitlists, preferences and capacities are deleted with the event
+        event::Entity::delete_by_id(event_id)
+            .exec(&transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(DeletedEvent { event, cancelled })
+    }
+
+    async fn find_active_event<C: ConnectionTrait>(
+        db: &C,
+        event_id: i32,
+    ) -> Result<event::Model, EventError> {
+        event::Entity::find_by_id(event_id)
+            .filter(in_active_edition(event::Column::EditionId))
+            .lock_exclusive()
             .one(db)
+            .await?
+            .ok_or(EventError::NotFound(event_id))
+    }
+
+    async fn count_event_subscribers<C: ConnectionTrait>(
+        db: &C,
+        event_id: i32,
+    ) -> Result<u64, DbErr> {
+        event_user::Entity::find()
+            .filter(event_user::Column::EventId.eq(event_id))
+            .count(db)
+            .await
+    }
+
+    /// Trims the fields of the event and checks them, the name must be unique in the active
+    /// edition
+    async fn validate_event<C: ConnectionTrait>(
+        db: &C,
+        event_id: Option<i32>,
+        event: EventToSave,
+        sections: Sections,
+    ) -> Result<EventToSave, EventError> {
+        let event = EventToSave {
+            name: event.name.trim().to_string(),
+            room: event.room.trim().to_string(),
+            zone: event.zone.trim().to_string(),
+            floor: event.floor.trim().to_string(),
+            ..event
+        };
+
+        if event.name.is_empty() {
+            return Err(EventError::InvalidEvent("The name is empty".to_string()));
+        }
+
+        if event.room.is_empty() {
+            return Err(EventError::InvalidEvent("The room is empty".to_string()));
+        }
+
+        if !sections.contains(event.minimum_section) {
+            return Err(EventError::InvalidEvent(format!(
+                "The minimum section must be between 1 and {}",
+                sections.max
+            )));
+        }
+
+        if !Rounds::load(db).await?.fits_in_a_day(event.round_span) {
+            return Err(EventError::InvalidEvent(format!(
+                "An event can't last {} rounds, no day has that many",
+                event.round_span
+            )));
+        }
+
+        let same_name = event::Entity::find()
+            .filter(in_active_edition(event::Column::EditionId))
+            .filter(event::Column::Name.eq(event.name.clone()))
+            .apply_if(event_id, |query, event_id| {
+                query.filter(event::Column::Id.ne(event_id))
+            })
+            .count(db)
             .await?;
 
-        let mut event_user: event_user::ActiveModel = event_user.unwrap().into();
+        if same_name > 0 {
+            return Err(EventError::DuplicateName(event.name));
+        }
 
-        event_user.joined_at = Set(Some(Utc::now().naive_utc()));
+        Ok(event)
+    }
 
-        event_user.update(db).await?;
+    /// Remove the users from the events, waitlists and preferences of the active edition,
+    /// offering the freed seats to the waitlisted students. The attendance of the past editions
+    /// is kept.
+    pub(crate) async fn release_seats<C: ConnectionTrait>(
+        db: &C,
+        user_ids: &[i32],
+        rules: SubscriptionRules,
+    ) -> Result<SubscriptionChanges, DbErr> {
+        let rounds = Rounds::load(db).await?;
+        let round_ids = rounds.ids().collect::<Vec<_>>();
 
-        Ok(())
+        let removed = event_user::Entity::find()
+            .filter(event_user::Column::UserId.is_in(user_ids.iter().copied()))
+            .filter(event_user::Column::Round.is_in(round_ids.iter().copied()))
+            .lock_exclusive()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|event_user| EventSubscription {
+                round: event_user.round,
+                event_id: event_user.event_id,
+            })
+            .collect::<Vec<_>>();
+
+        event_user::Entity::delete_many()
+            .filter(event_user::Column::UserId.is_in(user_ids.iter().copied()))
+            .filter(event_user::Column::Round.is_in(round_ids.iter().copied()))
+            .exec(db)
+            .await?;
+
+        event_waitlist::Entity::delete_many()
+            .filter(event_waitlist::Column::UserId.is_in(user_ids.iter().copied()))
+            .filter(event_waitlist::Column::Round.is_in(round_ids.iter().copied()))
+            .exec(db)
+            .await?;
+
+        event_preference::Entity::delete_many()
+            .filter(event_preference::Column::UserId.is_in(user_ids.iter().copied()))
+            .filter(event_preference::Column::Round.is_in(round_ids.iter().copied()))
+            .exec(db)
+            .await?;
+
+        let promotions = Self::promote_waitlisted(db, &removed, rules).await?;
+
+        Ok(SubscriptionChanges {
+            added: Vec::new(),
+            removed,
+            promotions,
+        })
+    }
+
+    /// Fill the freed seats with the students waiting for them, in the order they joined the
+    /// waitlist. A promoted student leaves the event it had in that round, whose seat is then
+    /// offered to its own waitlist.
+    ///
+    /// The students that picked the event in another round since joining the waitlist are
+    /// removed from it when repeated events aren't allowed, like the ones that picked a
+    /// multi-round event in the round.
+    pub(crate) async fn promote_waitlisted<C: ConnectionTrait>(
+        db: &C,
+        freed: &[EventSubscription],
+        rules: SubscriptionRules,
+    ) -> Result<Vec<WaitlistPromotion>, DbErr> {
+        let mut freed: VecDeque<EventSubscription> = freed.iter().copied().collect();
+        let mut promotions = Vec::new();
+
+        while let Some(seat) = freed.pop_front() {
+            let max_users = round_max_users::Entity::find_by_id((seat.round, seat.event_id))
+                .lock_exclusive()
+                .one(db)
+                .await?
+                .map(|m| m.max_users)
+                .unwrap_or(0);
+
+            let mut count = Self::count_subscribers(db, seat).await?;
+
+            while count < max_users.max(0) as u64 {
+                let Some(entry) = event_waitlist::Entity::find()
+                    .filter(event_waitlist::Column::EventId.eq(seat.event_id))
+                    .filter(event_waitlist::Column::Round.eq(seat.round))
+                    .order_by_asc(event_waitlist::Column::CreatedAt)
+                    .order_by_asc(event_waitlist::Column::UserId)
+                    .lock_exclusive()
+                    .one(db)
+                    .await?
+                else {
+                    break;
+                };
+
+                event_waitlist::Entity::delete_by_id((entry.user_id, entry.round))
+                    .exec(db)
+                    .await?;
+
+                if !rules.allow_repeated_events
+                    && Self::is_subscribed_in_another_round(db, entry.user_id, seat).await?
+                {
+                    continue;
+                }
+
+                let current = event_user::Entity::find_by_id((entry.user_id, entry.round))
+                    .one(db)
+                    .await?;
+
+                if let Some(current) = &current {
+                    if Self::is_multi_round_event(db, current.event_id).await? {
+                        continue;
+                    }
+                }
+
+                let previous_event_id = current.as_ref().map(|c| c.event_id);
+
+                match current {
+                    Some(current) => {
+                        freed.push_back(EventSubscription {
+                            round: current.round,
+                            event_id: current.event_id,
+                        });
+
+                        let mut current: event_user::ActiveModel = current.into();
+                        current.event_id = Set(entry.event_id);
+                        current.joined_at = Set(None);
+                        current.left_at = Set(None);
+                        current.update(db).await?;
+                    }
+                    None => {
+                        event_user::ActiveModel {
+                            user_id: Set(entry.user_id),
+                            joined_at: Default::default(),
+                            left_at: Default::default(),
+                            event_id: Set(entry.event_id),
+                            round: Set(entry.round),
+                        }
+                        .insert(db)
+                        .await?;
+                    }
+                }
+
+                promotions.push(WaitlistPromotion {
+                    user_id: entry.user_id,
+                    round: entry.round,
+                    event_id: entry.event_id,
+                    previous_event_id,
+                });
+
+                count += 1;
+            }
+        }
+
+        Ok(promotions)
+    }
+
+    async fn is_multi_round_event<C: ConnectionTrait>(
+        db: &C,
+        event_id: i32,
+    ) -> Result<bool, DbErr> {
+        let event = event::Entity::find_by_id(event_id).one(db).await?;
+
+        Ok(event.is_some_and(|event| event.round_span > 1))
     }
 
-    /// Leave an event (set the left_at field to now)
-    pub async fn leave_event(db: &DbConn, user_id: i32, event_id: i32) -> Result<(), DbErr> {
-        let event_user: Option<event_user::Model> = event_user::Entity::find()
+    /// Whether the user is subscribed to the event in a round other than the given one
+    async fn is_subscribed_in_another_round<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        subscription: EventSubscription,
+    ) -> Result<bool, DbErr> {
+        let count = event_user::Entity::find()
             .filter(event_user::Column::UserId.eq(user_id))
-            .filter(event_user::Column::EventId.eq(event_id))
-            .one(db)
+            .filter(event_user::Column::EventId.eq(subscription.event_id))
+            .filter(event_user::Column::Round.ne(subscription.round))
+            .count(db)
             .await?;
 
-        let mut event_user: event_user::ActiveModel = event_user.unwrap().into();
+        Ok(count > 0)
+    }
+
+    /// Count the users subscribed to an event in a round
+    async fn count_subscribers<C: ConnectionTrait>(
+        db: &C,
+        subscription: EventSubscription,
+    ) -> Result<u64, DbErr> {
+        event_user::Entity::find()
+            .filter(event_user::Column::EventId.eq(subscription.event_id))
+            .filter(event_user::Column::Round.eq(subscription.round))
+            .count(db)
+            .await
+    }
 
-        event_user.left_at = Set(Some(Utc::now().naive_utc()));
+    /// Checks that every round is chosen exactly once
+    fn validate_subscription_rounds(
+        subscriptions: &[EventSubscription],
+        rounds: &Rounds,
+    ) -> Result<(), SubscribeError> {
+        for subscription in subscriptions {
+            if !rounds.contains(subscription.round) {
+                return Err(SubscribeError::InvalidRounds(format!(
+                    "round {} doesn't exist",
+                    subscription.round
+                )));
+            }
+        }
+
+        for round in rounds.ids() {
+            match subscriptions.iter().filter(|s| s.round == round).count() {
+                0 => {
+                    return Err(SubscribeError::InvalidRounds(format!(
+                        "round {round} has no event"
+                    )))
+                }
+                1 => {}
+                _ => {
+                    return Err(SubscribeError::InvalidRounds(format!(
+                        "round {round} has more than one event"
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the rounds spanned by the multi-round events, from the first round they are picked
+    /// in, and checks that no event is picked in more than one round unless allowed
+    fn book_spanned_rounds(
+        subscriptions: &[EventSubscription],
+        round_spans: &HashMap<i32, i32>,
+        rounds: &Rounds,
+        rules: SubscriptionRules,
+    ) -> Result<Vec<EventSubscription>, SubscribeError> {
+        let mut rounds_by_event: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+        for subscription in subscriptions {
+            rounds_by_event
+                .entry(subscription.event_id)
+                .or_default()
+                .push(subscription.round);
+        }
+
+        let mut booked = Vec::with_capacity(subscriptions.len());
+        let mut repeated = Vec::new();
+        let mut past_last_round = Vec::new();
+
+        for (event_id, picked_rounds) in rounds_by_event {
+            let span = round_spans.get(&event_id).copied().unwrap_or(1);
+
+            // Every pick books the rounds it spans, the rounds already booked by a previous
+            // pick are part of it
+            let mut picks = 0;
+            let mut booked_rounds: Vec<i32> = Vec::new();
+
+            for round in rounds.ids().filter(|round| picked_rounds.contains(round)) {
+                if booked_rounds.contains(&round) {
+                    continue;
+                }
+
+                picks += 1;
+
+                match rounds.spanned_from(round, span) {
+                    Some(spanned) => booked_rounds.extend(spanned),
+                    None => {
+                        past_last_round.push(event_id);
+                        booked_rounds.push(round);
+                    }
+                }
+            }
+
+            // The rounds that don't exist are reported by `validate_subscription_rounds`
+            booked_rounds.extend(
+                picked_rounds
+                    .iter()
+                    .filter(|round| !rounds.contains(**round)),
+            );
+
+            booked.extend(
+                booked_rounds
+                    .into_iter()
+                    .map(|round| EventSubscription { round, event_id }),
+            );
+
+            if picks > 1 && !rules.allow_repeated_events {
+                repeated.push(event_id);
+            }
+        }
+
+        if !past_last_round.is_empty() {
+            return Err(SubscribeError::RoundSpanPastLastRound(past_last_round));
+        }
+
+        if !repeated.is_empty() {
+            return Err(SubscribeError::RepeatedEvents(repeated));
+        }
+
+        // Picking a different event in a round booked by a multi-round event
+        let overlapping = booked
+            .iter()
+            .filter(|s| {
+                booked.iter().any(|other| {
+                    other.round == s.round
+                        && other.event_id != s.event_id
+                        && (round_spans.contains_key(&s.event_id)
+                            || round_spans.contains_key(&other.event_id))
+                })
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        if !overlapping.is_empty() {
+            return Err(SubscribeError::OverlappingRounds(overlapping));
+        }
+
+        booked.sort_unstable_by_key(|s| (s.round, s.event_id));
+
+        Ok(booked)
+    }
+
+    /// Checks that every round is ranked exactly once, with distinct events
+    fn validate_preferences(
+        preferences: &[RoundPreferences],
+        rounds: &Rounds,
+    ) -> Result<(), PreferencesError> {
+        for preference in preferences {
+            if !rounds.contains(preference.round) {
+                return Err(PreferencesError::InvalidPreferences(format!(
+                    "round {} doesn't exist",
+                    preference.round
+                )));
+            }
+
+            if preference.event_ids.is_empty() {
+                return Err(PreferencesError::InvalidPreferences(format!(
+                    "round {} has no event",
+                    preference.round
+                )));
+            }
 
-        event_user.update(db).await?;
+            for (index, event_id) in preference.event_ids.iter().enumerate() {
+                if preference.event_ids[..index].contains(event_id) {
+                    return Err(PreferencesError::InvalidPreferences(format!(
+                        "event {event_id} is ranked more than once in round {}",
+                        preference.round
+                    )));
+                }
+            }
+        }
+
+        for round in rounds.ids() {
+            match preferences.iter().filter(|p| p.round == round).count() {
+                0 => {
+                    return Err(PreferencesError::InvalidPreferences(format!(
+                        "round {round} is not ranked"
+                    )))
+                }
+                1 => {}
+                _ => {
+                    return Err(PreferencesError::InvalidPreferences(format!(
+                        "round {round} is ranked more than once"
+                    )))
+                }
+            }
+        }
 
         Ok(())
     }
+
+    /// Returns the events whose minimum section is above the section of the user
+    async fn not_eligible_events_for_subscriptions<C: ConnectionTrait>(
+        db: &C,
+        user: &user::Model,
+        subscriptions: &[EventSubscription],
+    ) -> Result<Vec<i32>, DbErr> {
+        let event_ids = subscriptions.iter().map(|s| s.event_id).collect::<Vec<_>>();
+
+        let events = event::Entity::find()
+            .filter(event::Column::Id.is_in(event_ids))
+            .filter(event::Column::MinimumSection.gt(user.section))
+            .order_by_asc(event::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(events.into_iter().map(|event| event.id).collect())
+    }
+
+    /// Returns the subscriptions to events with no seats left in their round,
+    /// an event without capacity for a round (or with a capacity of 0) doesn't run in that round.
+    ///
+    /// The `round_max_users` rows are locked until the end of the transaction.
+    async fn full_events_for_subscriptions<C: ConnectionTrait>(
+        db: &C,
+        subscriptions: &[EventSubscription],
+    ) -> Result<Vec<EventSubscription>, DbErr> {
+        let mut condition = Condition::any();
+        for subscription in subscriptions {
+            condition = condition.add(
+                round_max_users::Column::Round
+                    .eq(subscription.round)
+                    .and(round_max_users::Column::EventId.eq(subscription.event_id)),
+            );
+        }
+
+        let max_users = round_max_users::Entity::find()
+            .filter(condition)
+            .order_by_asc(round_max_users::Column::EventId)
+            .order_by_asc(round_max_users::Column::Round)
+            .lock_exclusive()
+            .all(db)
+            .await?;
+
+        let mut full_events = Vec::new();
+
+        for subscription in subscriptions {
+            let max_users = max_users
+                .iter()
+                .find(|m| m.round == subscription.round && m.event_id == subscription.event_id)
+                .map(|m| m.max_users)
+                .unwrap_or(0);
+
+            let count = Self::count_subscribers(db, *subscription).await?;
+
+            if count >= max_users.max(0) as u64 {
+                full_events.push(*subscription);
+            }
+        }
+
+        Ok(full_events)
+    }
+
+    /// Join an event (set the joined_at field to now) in a round, returns `None` when the user is not subscribed to the event in
+    /// that round
+    pub async fn join_event(
+        db: &DbConn,
+        user_id: i32,
+        subscription: EventSubscription,
+    ) -> Result<Option<event_user::Model>, DbErr> {
+        let Some(event_user) = event_user::Entity::find_by_id((user_id, subscription.round))
+            .filter(event_user::Column::EventId.eq(subscription.event_id))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut event_user: event_user::ActiveModel = event_user.into();
+
+        event_user.joined_at = Set(Some(Utc::now().naive_utc()));
+
+        Ok(Some(event_user.update(db).await?))
+    }
+
+    /// Leave an event (set the left_at field to now) in a round, returns `None` when the user is not subscribed to the event in
+    /// that round
+    pub async fn leave_event(
+        db: &DbConn,
+        user_id: i32,
+        subscription: EventSubscription,
+    ) -> Result<Option<event_user::Model>, DbErr> {
+        let Some(event_user) = event_user::Entity::find_by_id((user_id, subscription.round))
+            .filter(event_user::Column::EventId.eq(subscription.event_id))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut event_user: event_user::ActiveModel = event_user.into();
+
+        event_user.left_at = Set(Some(Utc::now().naive_utc()));
+
+        Ok(Some(event_user.update(db).await?))
+    }
 }
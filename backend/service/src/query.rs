@@ -1,8 +1,27 @@
-use ::entity::{event, event_user, prelude::*, user};
+use crate::edition::{in_active_edition, in_active_edition_rounds};
+use crate::Rounds;
+use ::entity::{
+    event, event_preference, event_user, prelude::*, registration_window, round, round_max_users,
+    user,
+};
+use sea_orm::sea_query::{Expr, Func, LikeExpr, SimpleExpr};
 use sea_orm::*;
 
 pub struct Query;
 
+/// Matches the rows whose column contains the text, ignoring case. The `%` and `_` of the text
+/// are matched literally instead of as wildcards.
+pub(crate) fn contains_ignoring_case(column: impl ColumnTrait, text: &str) -> SimpleExpr {
+    let escaped = text
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    Expr::expr(Func::lower(Expr::col(column)))
+        .like(LikeExpr::new(format!("%{escaped}%")).escape('\\'))
+}
+
 #[derive(Debug)]
 pub struct EventUserStatus {
     pub id: i32,
@@ -14,15 +33,25 @@ pub struct EventUserStatus {
     pub left_at: Option<chrono::NaiveDateTime>,
 }
 
-#[derive(Debug)]
-pub struct EventCounterStatus {
+/// The seats of an event in a round it runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventRoundSeats {
     pub event_id: i32,
-    pub count: u64,
+    pub round: i32,
+    pub max_users: i32,
+    /// The students subscribed to the event in the round
+    pub registered: u64,
 }
 
 impl Query {
+    pub async fn get_user_by_id(db: &DbConn, user_id: i32) -> Result<Option<user::Model>, DbErr> {
+        User::find_by_id(user_id).one(db).await
+    }
+
+    /// Returns the events of the active edition
     pub async fn get_all_events(db: &DbConn) -> Result<Vec<event::Model>, DbErr> {
         let events = Event::find()
+            .filter(in_active_edition(event::Column::EditionId))
             .order_by(event::Column::Id, Order::Asc)
             .all(db)
             .await?;
@@ -30,33 +59,122 @@ impl Query {
         Ok(events)
     }
 
-    pub async fn get_event_user_count_by_id(db: &DbConn, event_id: i32) -> Result<u64, DbErr> {
-        let count = EventUser::find()
-            .filter(event_user::Column::EventId.eq(event_id))
-            .count(db)
-            .await?;
+    /// Returns the rounds of the active edition in chronological order
+    pub async fn get_rounds(db: &DbConn) -> Result<Vec<round::Model>, DbErr> {
+        Ok(Rounds::load(db).await?.into())
+    }
 
-        Ok(count)
+    /// Returns the ranked preferences of the user in the active edition, by round and rank
+    pub async fn get_preferences_by_user_id(
+        db: &DbConn,
+        user_id: i32,
+    ) -> Result<Vec<event_preference::Model>, DbErr> {
+        EventPreference::find()
+            .filter(event_preference::Column::UserId.eq(user_id))
+            .filter(in_active_edition_rounds(event_preference::Column::Round))
+            .order_by_asc(event_preference::Column::Round)
+            .order_by_asc(event_preference::Column::Rank)
+            .all(db)
+            .await
     }
 
-    pub async fn get_events_user_count_by_ids(
+    /// Returns the registration window that applies to the user: the one of its class,
+    /// otherwise the one of its section, otherwise the one for everyone
+    pub async fn registration_window_for_user(
         db: &DbConn,
-        event_ids: Vec<i32>,
-    ) -> Result<Vec<EventCounterStatus>, DbErr> {
-        let mut counts = Vec::with_capacity(event_ids.len());
+        user: &user::Model,
+    ) -> Result<Option<registration_window::Model>, DbErr> {
+        let class_condition = match &user.class {
+            Some(class) => registration_window::Column::Class
+                .is_null()
+                .or(registration_window::Column::Class.eq(class.clone())),
+            None => registration_window::Column::Class.is_null(),
+        };
+
+        let windows = RegistrationWindow::find()
+            .filter(in_active_edition(registration_window::Column::EditionId))
+            .filter(class_condition)
+            .filter(
+                registration_window::Column::Section
+                    .is_null()
+                    .or(registration_window::Column::Section.eq(user.section)),
+            )
+            .all(db)
+            .await?;
 
-        for event_id in event_ids {
-            let count = EventUser::find()
-                .filter(event_user::Column::EventId.eq(event_id))
-                .count(db)
-                .await?;
+        let window = windows
+            .into_iter()
+            .max_by_key(|window| (window.class.is_some(), window.section.is_some(), window.id));
 
-            counts.push(EventCounterStatus { event_id, count });
-        }
+        Ok(window)
+    }
+
+    /// Returns the seats of every event of the active edition in the rounds it runs in, by event
+    /// and round
+    pub async fn get_events_seats(db: &DbConn) -> Result<Vec<EventRoundSeats>, DbErr> {
+        Self::events_seats(db, None).await
+    }
 
-        Ok(counts)
+    /// Returns the seats of the given events in the rounds of the active edition they run in, by
+    /// event and round
+    pub async fn get_events_seats_by_ids(
+        db: &DbConn,
+        event_ids: &[i32],
+    ) -> Result<Vec<EventRoundSeats>, DbErr> {
+        Self::events_seats(db, Some(event_ids)).await
+    }
+
+    /// Counts the subscriptions of every event and round in a single grouped query
+    async fn events_seats(
+        db: &DbConn,
+        event_ids: Option<&[i32]>,
+    ) -> Result<Vec<EventRoundSeats>, DbErr> {
+        let mut query = RoundMaxUsers::find()
+            .select_only()
+            .column(round_max_users::Column::EventId)
+            .column(round_max_users::Column::Round)
+            .column(round_max_users::Column::MaxUsers)
+            .column_as(event_user::Column::UserId.count(), "registered")
+            .filter(in_active_edition_rounds(round_max_users::Column::Round))
+            .filter(round_max_users::Column::MaxUsers.gt(0))
+            .apply_if(event_ids, |query, event_ids| {
+                query.filter(round_max_users::Column::EventId.is_in(event_ids.iter().copied()))
+            })
+            .group_by(round_max_users::Column::EventId)
+            .group_by(round_max_users::Column::Round)
+            .group_by(round_max_users::Column::MaxUsers)
+            .order_by_asc(round_max_users::Column::EventId)
+            .order_by_asc(round_max_users::Column::Round);
+
+        // The subscriptions to the event in the round
+        QueryTrait::query(&mut query).join(
+            JoinType::LeftJoin,
+            EventUser,
+            Expr::col((EventUser, event_user::Column::EventId))
+                .equals((RoundMaxUsers, round_max_users::Column::EventId))
+                .and(
+                    Expr::col((EventUser, event_user::Column::Round))
+                        .equals((RoundMaxUsers, round_max_users::Column::Round)),
+                ),
+        );
+
+        let seats = query
+            .into_tuple::<(i32, i32, i32, i64)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|(event_id, round, max_users, registered)| EventRoundSeats {
+                event_id,
+                round,
+                max_users,
+                registered: registered as u64,
+            })
+            .collect();
+
+        Ok(seats)
     }
 
+    /// Returns the students subscribed to the event in a round of the active edition
     pub async fn event_users_status(
         db: &DbConn,
         event_id: i32,
@@ -65,6 +183,7 @@ impl Query {
         let event_users: Vec<event_user::Model> = event_user::Entity::find()
             .filter(event_user::Column::EventId.eq(event_id))
             .filter(event_user::Column::Round.eq(round))
+            .filter(in_active_edition_rounds(event_user::Column::Round))
             .all(db)
             .await?;
 
@@ -0,0 +1,169 @@
+use crate::edition::in_active_edition;
+use crate::{Mutation, Query, Sections};
+use ::entity::{edition, registration_window};
+use chrono::NaiveDateTime;
+use sea_orm::*;
+
+/// A registration window applying to everyone, to a section or to a class
+#[derive(Debug, Clone)]
+pub struct RegistrationWindowToSave {
+    pub section: Option<i32>,
+    /// An empty class applies to every class
+    pub class: Option<String>,
+    pub opens_at: NaiveDateTime,
+    pub closes_at: NaiveDateTime,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WindowError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("{0}")]
+    InvalidWindow(String),
+
+    #[error("Registration window {0} not found")]
+    NotFound(i32),
+
+    #[error("Edition {0} not found")]
+    EditionNotFound(i32),
+
+    #[error("Edition {0} is archived")]
+    ArchivedEdition(i32),
+
+    #[error("There is no active edition to add the registration window to")]
+    NoActiveEdition,
+}
+
+impl Query {
+    /// Returns the registration windows of an edition, the active one when not given
+    pub async fn get_registration_windows(
+        db: &DbConn,
+        edition_id: Option<i32>,
+    ) -> Result<Vec<registration_window::Model>, DbErr> {
+        registration_window::Entity::find()
+            .filter(match edition_id {
+                Some(edition_id) => registration_window::Column::EditionId.eq(edition_id),
+                None => in_active_edition(registration_window::Column::EditionId),
+            })
+            .order_by_asc(registration_window::Column::OpensAt)
+            .order_by_asc(registration_window::Column::Id)
+            .all(db)
+            .await
+    }
+}
+
+impl Mutation {
+    /// Add a registration window to an edition, the active one when not given. The windows of
+    /// an archived edition can't be changed anymore.
+    pub async fn create_registration_window(
+        db: &DbConn,
+        edition_id: Option<i32>,
+        window: RegistrationWindowToSave,
+        sections: Sections,
+    ) -> Result<registration_window::Model, WindowError> {
+        let window = validate_window(window, sections)?;
+
+        let edition = match edition_id {
+            Some(edition_id) => edition::Entity::find_by_id(edition_id)
+                .one(db)
+                .await?
+                .ok_or(WindowError::EditionNotFound(edition_id))?,
+            None => edition::Entity::find()
+                .filter(edition::Column::Active.eq(true))
+                .one(db)
+                .await?
+                .ok_or(WindowError::NoActiveEdition)?,
+        };
+
+        if edition.archived_at.is_some() {
+            return Err(WindowError::ArchivedEdition(edition.id));
+        }
+
+        let window = registration_window::ActiveModel {
+            section: Set(window.section),
+            class: Set(window.class),
+            opens_at: Set(window.opens_at),
+            closes_at: Set(window.closes_at),
+            edition_id: Set(edition.id),
+            ..Default::default()
+        };
+
+        Ok(window.insert(db).await?)
+    }
+
+    /// Change a registration window, it stays in its edition
+    pub async fn update_registration_window(
+        db: &DbConn,
+        window_id: i32,
+        window: RegistrationWindowToSave,
+        sections: Sections,
+    ) -> Result<registration_window::Model, WindowError> {
+        let window = validate_window(window, sections)?;
+
+        let current = editable_window(db, window_id).await?;
+
+        let mut current: registration_window::ActiveModel = current.into();
+        current.section = Set(window.section);
+        current.class = Set(window.class);
+        current.opens_at = Set(window.opens_at);
+        current.closes_at = Set(window.closes_at);
+
+        Ok(current.update(db).await?)
+    }
+
+    /// Delete a registration window, the students it applied to follow the next most specific
+    /// one, or have the registrations always open without any
+    pub async fn delete_registration_window(
+        db: &DbConn,
+        window_id: i32,
+    ) -> Result<(), WindowError> {
+        editable_window(db, window_id).await?.delete(db).await?;
+
+        Ok(())
+    }
+}
+
+/// The registration window, when its edition isn't archived
+async fn editable_window(
+    db: &DbConn,
+    window_id: i32,
+) -> Result<registration_window::Model, WindowError> {
+    let (window, edition) = registration_window::Entity::find_by_id(window_id)
+        .find_also_related(edition::Entity)
+        .one(db)
+        .await?
+        .ok_or(WindowError::NotFound(window_id))?;
+
+    if edition.is_some_and(|edition| edition.archived_at.is_some()) {
+        return Err(WindowError::ArchivedEdition(window.edition_id));
+    }
+
+    Ok(window)
+}
+
+/// Trims the class and checks the window, it must close after it opens
+fn validate_window(
+    window: RegistrationWindowToSave,
+    sections: Sections,
+) -> Result<RegistrationWindowToSave, WindowError> {
+    let class = window
+        .class
+        .map(|class| class.trim().to_string())
+        .filter(|class| !class.is_empty());
+
+    if window.opens_at >= window.closes_at {
+        return Err(WindowError::InvalidWindow(
+            "The window must close after it opens".to_string(),
+        ));
+    }
+
+    if let Some(section) = window.section.filter(|s| !sections.contains(*s)) {
+        return Err(WindowError::InvalidWindow(format!(
+            "The section must be between 1 and {}, got {section}",
+            sections.max
+        )));
+    }
+
+    Ok(RegistrationWindowToSave { class, ..window })
+}
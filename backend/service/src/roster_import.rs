@@ -0,0 +1,400 @@
+use crate::{Mutation, Sections, SubscriptionChanges, SubscriptionRules};
+use ::entity::{sea_orm_active_enums::UserRole, user};
+use csv::{ReaderBuilder, StringRecord, Trim};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RosterImportOptions {
+    /// Deactivate the students missing from the roster, freeing their seats in the active edition
+    pub deactivate_missing: bool,
+    /// Only validate the rows and report the changes, without saving them
+    pub dry_run: bool,
+    /// The rules the waitlisted students taking the freed seats must follow
+    pub rules: SubscriptionRules,
+    pub sections: Sections,
+}
+
+/// An invalid row, numbered as the lines of the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RosterImportRowError {
+    pub row: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RosterImportOutcome {
+    pub created: usize,
+    pub updated: usize,
+    /// The students already matching their row
+    pub unchanged: usize,
+    /// The students missing from the roster that were deactivated
+    pub deactivated: Vec<i32>,
+    /// Nothing is saved when a row is invalid
+    pub errors: Vec<RosterImportRowError>,
+    /// The seats freed by the deactivated students, and the waitlisted students that took them
+    pub changes: SubscriptionChanges,
+    /// Whether the roster was saved
+    pub applied: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RosterImportError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("Failed to read the roster: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("The roster has no column {0:?}")]
+    MissingColumn(&'static str),
+}
+
+/// A student read from a valid row
+#[derive(Debug)]
+struct RosterStudent {
+    name: String,
+    email: String,
+    section: i32,
+    class: Option<String>,
+}
+
+pub struct RosterImport;
+
+impl RosterImport {
+    /// Create or update the students from a CSV roster with the `name`, `email`, `section` and
+    /// `class` columns. The students are matched by email, the existing ones are reactivated and
+    /// keep their role.
+    ///
+    /// Every row is validated first, nothing is saved when one of them is invalid or when an
+    /// email is repeated. Importing the same roster again changes nothing.
+    pub async fn import_csv(
+        db: &DbConn,
+        roster: &[u8],
+        options: RosterImportOptions,
+    ) -> Result<RosterImportOutcome, RosterImportError> {
+        let (students, errors) = Self::parse_roster(roster, options.sections)?;
+
+        if !errors.is_empty() {
+            return Ok(RosterImportOutcome {
+                errors,
+                ..Default::default()
+            });
+        }
+
+        let transaction = db.begin().await?;
+
+        let existing = user::Entity::find()
+            .lock_exclusive()
+            .all(&transaction)
+            .await?
+            .into_iter()
+            .map(|user| (user.email.to_lowercase(), user))
+            .collect::<HashMap<_, _>>();
+
+        let mut outcome = RosterImportOutcome::default();
+
+        for student in &students {
+            match existing.get(&student.email) {
+                Some(user)
+                    if user.active
+                        && user.name.as_ref() == Some(&student.name)
+                        && user.section == student.section
+                        && user.class == student.class =>
+                {
+                    outcome.unchanged += 1;
+                }
+                Some(user) => {
+                    let mut user: user::ActiveModel = user.clone().into();
+                    user.name = Set(Some(student.name.clone()));
+                    user.section = Set(student.section);
+                    user.class = Set(student.class.clone());
+                    user.active = Set(true);
+                    user.update(&transaction).await?;
+
+                    outcome.updated += 1;
+                }
+                None => {
+                    user::Entity::insert(user::ActiveModel {
+                        name: Set(Some(student.name.clone())),
+                        email: Set(student.email.clone()),
+                        section: Set(student.section),
+                        class: Set(student.class.clone()),
+                        role: Set(UserRole::Student),
+                        ..Default::default()
+                    })
+                    .exec(&transaction)
+                    .await?;
+
+                    outcome.created += 1;
+                }
+            }
+        }
+
+        if options.deactivate_missing {
+            let emails = students
+                .iter()
+                .map(|student| student.email.as_str())
+                .collect::<HashSet<_>>();
+
+            let mut missing = existing
+                .iter()
+                .filter(|(email, user)| {
+                    user.role == UserRole::Student
+                        && user.active
+                        && !emails.contains(email.as_str())
+                })
+                .map(|(_, user)| user.id)
+                .collect::<Vec<_>>();
+            missing.sort_unstable();
+
+            if !missing.is_empty() {
+                user::Entity::update_many()
+                    .col_expr(user::Column::Active, Expr::value(false))
+                    .filter(user::Column::Id.is_in(missing.iter().copied()))
+                    .exec(&transaction)
+                    .await?;
+
+                outcome.changes =
+                    Mutation::release_seats(&transaction, &missing, options.rules).await?;
+            }
+
+            outcome.deactivated = missing;
+        }
+
+        if options.dry_run {
+            transaction.rollback().await?;
+        } else {
+            transaction.commit().await?;
+            outcome.applied = true;
+        }
+
+        Ok(outcome)
+    }
+
+    fn parse_roster(
+        roster: &[u8],
+        sections: Sections,
+    ) -> Result<(Vec<RosterStudent>, Vec<RosterImportRowError>), RosterImportError> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .trim(Trim::All)
+            .from_reader(roster);
+
+        let headers = reader.headers()?.clone();
+        let index = |column: &'static str| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column))
+                .ok_or(RosterImportError::MissingColumn(column))
+        };
+        let (name, email, section, class) = (
+            index("name")?,
+            index("email")?,
+            index("section")?,
+            index("class")?,
+        );
+
+        let mut students = Vec::new();
+        let mut errors = Vec::new();
+        let mut rows_by_email: HashMap<String, u64> = HashMap::new();
+
+        for result in reader.records() {
+            let record = match result {
+                Ok(record) => record,
+                Err(err) => {
+                    errors.push(RosterImportRowError {
+                        row: err.position().map(|p| p.line()).unwrap_or_default(),
+                        message: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            if record.iter().all(|field| field.is_empty()) {
+                continue;
+            }
+
+            let row = record.position().map(|p| p.line()).unwrap_or_default();
+
+            match Self::parse_student(&record, name, email, section, class, sections) {
+                Ok(student) => match rows_by_email.get(&student.email) {
+                    Some(previous_row) => errors.push(RosterImportRowError {
+                        row,
+                        message: format!(
+                            "The email {} is already on row {previous_row}",
+                            student.email
+                        ),
+                    }),
+                    None => {
+                        rows_by_email.insert(student.email.clone(), row);
+                        students.push(student);
+                    }
+                },
+                Err(message) => errors.push(RosterImportRowError { row, message }),
+            }
+        }
+
+        Ok((students, errors))
+    }
+
+    fn parse_student(
+        record: &StringRecord,
+        name: usize,
+        email: usize,
+        section: usize,
+        class: usize,
+        sections: Sections,
+    ) -> Result<RosterStudent, String> {
+        let field = |index: usize| record.get(index).unwrap_or_default();
+
+        if field(name).is_empty() {
+            return Err("The name is missing".to_string());
+        }
+
+        let is_valid_email = field(email).split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && domain.contains('.') && !domain.contains('@')
+        });
+        if !is_valid_email {
+            return Err(format!("{:?} is not a valid email", field(email)));
+        }
+
+        let Some(section_number) = field(section)
+            .parse()
+            .ok()
+            .filter(|s: &i32| sections.contains(*s))
+        else {
+            return Err(format!(
+                "{:?} is not a valid section, it must be between 1 and {}",
+                field(section),
+                sections.max
+            ));
+        };
+
+        Ok(RosterStudent {
+            name: field(name).to_string(),
+            email: field(email).to_lowercase(),
+            section: section_number,
+            class: Some(field(class).to_string()).filter(|class| !class.is_empty()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(roster: &str) -> (Vec<RosterStudent>, Vec<RosterImportRowError>) {
+        RosterImport::parse_roster(roster.as_bytes(), Sections::default()).unwrap()
+    }
+
+    fn record(fields: &[&str]) -> StringRecord {
+        StringRecord::from(fields.to_vec())
+    }
+
+    #[test]
+    fn reads_the_students() {
+        let (students, errors) = parse(
+            "Email, Name, Section, Class\n\
+             Ada@Example.org, Ada Lovelace, 3, 3A\n\
+             alan@example.org, Alan Turing, 5,\n",
+        );
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(students.len(), 2);
+
+        assert_eq!(students[0].name, "Ada Lovelace");
+        assert_eq!(students[0].email, "ada@example.org");
+        assert_eq!(students[0].section, 3);
+        assert_eq!(students[0].class.as_deref(), Some("3A"));
+
+        assert_eq!(students[1].class, None);
+    }
+
+    #[test]
+    fn reports_the_malformed_rows() {
+        let (students, errors) = parse(
+            "name,email,section,class\n\
+             ,nobody@example.org,1,1A\n\
+             Ada Lovelace,ada.example.org,1,1A\n\
+             Alan Turing,alan@localhost,1,1A\n\
+             Grace Hopper,grace@example.org,0,1A\n\
+             Edsger Dijkstra,edsger@example.org,first,1A\n\
+             Barbara Liskov,barbara@example.org,2,2A\n",
+        );
+
+        assert_eq!(students.len(), 1);
+        assert_eq!(students[0].name, "Barbara Liskov");
+        assert_eq!(
+            errors.iter().map(|error| error.row).collect::<Vec<_>>(),
+            vec![2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn reports_the_repeated_emails() {
+        let (students, errors) = parse(
+            "name,email,section,class\n\
+             Ada Lovelace,ada@example.org,1,1A\n\
+             Alan Turing,alan@example.org,1,1A\n\
+             Ada King,ADA@example.org,2,2A\n",
+        );
+
+        assert_eq!(students.len(), 2);
+        assert_eq!(
+            errors,
+            vec![RosterImportRowError {
+                row: 4,
+                message: "The email ada@example.org is already on row 2".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn skips_the_empty_rows() {
+        let (students, errors) = parse(
+            "name,email,section,class\n\
+             ,,,\n\
+             Ada Lovelace,ada@example.org,1,1A\n\
+             \n\
+             , , ,\n",
+        );
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(students.len(), 1);
+    }
+
+    #[test]
+    fn requires_every_column() {
+        assert!(matches!(
+            RosterImport::parse_roster(
+                b"name,email,class\nAda,ada@example.org,1A\n",
+                Sections::default()
+            ),
+            Err(RosterImportError::MissingColumn("section"))
+        ));
+    }
+
+    #[test]
+    fn validates_a_student() {
+        let student = |fields: &[&str]| {
+            RosterImport::parse_student(&record(fields), 0, 1, 2, 3, Sections { max: 3 })
+        };
+
+        assert!(student(&["Ada", "ada@example.org", "1", ""]).is_ok());
+        assert!(student(&["Ada", "ada@example.org", "1"]).is_ok());
+        assert_eq!(
+            student(&["Ada", "ada@example@org", "1", ""]).unwrap_err(),
+            "\"ada@example@org\" is not a valid email"
+        );
+        assert!(student(&["Ada", "ada@example.org", "-1", ""]).is_err());
+        assert_eq!(
+            student(&["Ada", "ada@example.org", "4", ""]).unwrap_err(),
+            "\"4\" is not a valid section, it must be between 1 and 3"
+        );
+        assert!(student(&["Ada", "ada@example.org"]).is_err());
+        assert!(student(&["", "ada@example.org", "1", ""]).is_err());
+    }
+}
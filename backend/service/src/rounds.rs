@@ -0,0 +1,65 @@
+use ::entity::round;
+use sea_orm::*;
+
+use crate::edition::in_active_edition;
+
+/// The rounds of the active edition of the forum, in chronological order
+#[derive(Debug, Clone, Default)]
+pub struct Rounds(Vec<round::Model>);
+
+impl Rounds {
+    pub async fn load<C: ConnectionTrait>(db: &C) -> Result<Self, DbErr> {
+        let rounds = round::Entity::find()
+            .filter(in_active_edition(round::Column::EditionId))
+            .order_by_asc(round::Column::Day)
+            .order_by_asc(round::Column::StartTime)
+            .order_by_asc(round::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(Self(rounds))
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.0.iter().map(|round| round.id)
+    }
+
+    pub fn contains(&self, round_id: i32) -> bool {
+        self.0.iter().any(|round| round.id == round_id)
+    }
+
+    /// The `span` consecutive rounds of the same day starting from the given one,
+    /// `None` when the day doesn't have enough rounds left
+    pub fn spanned_from(&self, round_id: i32, span: i32) -> Option<Vec<i32>> {
+        let start = self.0.iter().position(|round| round.id == round_id)?;
+        let spanned = self.0.get(start..start + span.max(1) as usize)?;
+
+        spanned
+            .iter()
+            .all(|round| round.day == self.0[start].day)
+            .then(|| spanned.iter().map(|round| round.id).collect())
+    }
+
+    /// Whether an event lasting `span` rounds fits in one of the days, any span of at least one
+    /// round fits before the rounds are set
+    pub fn fits_in_a_day(&self, span: i32) -> bool {
+        span >= 1
+            && (self.0.is_empty()
+                || self
+                    .ids()
+                    .any(|round| self.spanned_from(round, span).is_some()))
+    }
+}
+
+/// The rounds are expected in chronological order
+impl From<Vec<round::Model>> for Rounds {
+    fn from(rounds: Vec<round::Model>) -> Self {
+        Self(rounds)
+    }
+}
+
+impl From<Rounds> for Vec<round::Model> {
+    fn from(rounds: Rounds) -> Self {
+        rounds.0
+    }
+}
@@ -0,0 +1,184 @@
+use crate::query::contains_ignoring_case;
+use crate::{Mutation, Query, Sections, SubscriptionChanges, SubscriptionRules};
+use ::entity::{sea_orm_active_enums::UserRole, user};
+use sea_orm::*;
+
+/// The most users returned by a page of a search
+pub const MAX_USERS_PAGE_SIZE: u64 = 200;
+
+/// The empty fields match every user
+#[derive(Debug, Clone, Default)]
+pub struct UserSearch {
+    /// Matched against the name and the email, ignoring case
+    pub text: Option<String>,
+    pub class: Option<String>,
+    pub section: Option<i32>,
+    pub role: Option<UserRole>,
+    pub include_inactive: bool,
+    /// Starting from 0
+    pub page: u64,
+    /// Capped to `MAX_USERS_PAGE_SIZE`
+    pub page_size: u64,
+}
+
+#[derive(Debug)]
+pub struct UserPage {
+    pub users: Vec<user::Model>,
+    /// The users matching the search, on every page
+    pub total: u64,
+    pub pages: u64,
+}
+
+/// The fields of a user changed by an admin, the ones not set are left unchanged
+#[derive(Debug, Clone, Default)]
+pub struct UserUpdate {
+    pub section: Option<i32>,
+    /// An empty class clears it
+    pub class: Option<String>,
+    pub role: Option<UserRole>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UserError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+
+    #[error("User {0} not found")]
+    NotFound(i32),
+
+    #[error("The section must be between 1 and {1}, got {0}")]
+    InvalidSection(i32, i32),
+}
+
+impl Query {
+    /// Returns a page of the users matching the search, ordered by name and email
+    pub async fn search_users(db: &DbConn, search: &UserSearch) -> Result<UserPage, DbErr> {
+        let page_size = search.page_size.clamp(1, MAX_USERS_PAGE_SIZE);
+
+        let paginator = user::Entity::find()
+            .apply_if(search.text.as_deref(), |query, text| {
+                query.filter(
+                    Condition::any()
+                        .add(contains_ignoring_case(user::Column::Name, text))
+                        .add(contains_ignoring_case(user::Column::Email, text)),
+                )
+            })
+            .apply_if(search.class.as_deref(), |query, class| {
+                query.filter(user::Column::Class.eq(class))
+            })
+            .apply_if(search.section, |query, section| {
+                query.filter(user::Column::Section.eq(section))
+            })
+            .apply_if(search.role.clone(), |query, role| {
+                query.filter(user::Column::Role.eq(role))
+            })
+            .apply_if(
+                (!search.include_inactive).then_some(true),
+                |query, active| query.filter(user::Column::Active.eq(active)),
+            )
+            .order_by_asc(user::Column::Name)
+            .order_by_asc(user::Column::Email)
+            .paginate(db, page_size);
+
+        let totals = paginator.num_items_and_pages().await?;
+        let users = paginator.fetch_page(search.page).await?;
+
+        Ok(UserPage {
+            users,
+            total: totals.number_of_items,
+            pages: totals.number_of_pages,
+        })
+    }
+}
+
+impl Mutation {
+    /// Change the section, the class or the role of a user. Its subscriptions are kept, even
+    /// when its new section is below the minimum section of its events.
+    pub async fn update_user(
+        db: &DbConn,
+        user_id: i32,
+        update: UserUpdate,
+        sections: Sections,
+    ) -> Result<user::Model, UserError> {
+        if let Some(section) = update.section.filter(|s| !sections.contains(*s)) {
+            return Err(UserError::InvalidSection(section, sections.max));
+        }
+
+        let user = user::Entity::find_by_id(user_id)
+            .one(db)
+            .await?
+            .ok_or(UserError::NotFound(user_id))?;
+
+        let mut user: user::ActiveModel = user.into();
+        if let Some(section) = update.section {
+            user.section = Set(section);
+        }
+        if let Some(class) = update.class {
+            let class = class.trim();
+            user.class = Set(Some(class.to_string()).filter(|class| !class.is_empty()));
+        }
+        if let Some(role) = update.role {
+            user.role = Set(role);
+        }
+
+        Ok(user.update(db).await?)
+    }
+
+    /// Deactivate or reactivate a user. A deactivated user can't log in anymore, and leaves the
+    /// events, waitlists and preferences of the active edition, the freed seats are offered to
+    /// the waitlisted students.
+    pub async fn set_user_active(
+        db: &DbConn,
+        user_id: i32,
+        active: bool,
+        rules: SubscriptionRules,
+    ) -> Result<SubscriptionChanges, UserError> {
+        let transaction = db.begin().await?;
+
+        let user = user::Entity::find_by_id(user_id)
+            .lock_exclusive()
+            .one(&transaction)
+            .await?
+            .ok_or(UserError::NotFound(user_id))?;
+
+        let mut user: user::ActiveModel = user.into();
+        user.active = Set(active);
+        user.update(&transaction).await?;
+
+        let changes = if active {
+            SubscriptionChanges::default()
+        } else {
+            Self::release_seats(&transaction, &[user_id], rules).await?
+        };
+
+        transaction.commit().await?;
+
+        Ok(changes)
+    }
+
+    /// Delete a user along with its subscriptions, waitlists and preferences, including the
+    /// attendance of the past editions. The seats it had in the active edition are offered to
+    /// the waitlisted students.
+    pub async fn delete_user(
+        db: &DbConn,
+        user_id: i32,
+        rules: SubscriptionRules,
+    ) -> Result<SubscriptionChanges, UserError> {
+        let transaction = db.begin().await?;
+
+        let user = user::Entity::find_by_id(user_id)
+            .lock_exclusive()
+            .one(&transaction)
+            .await?
+            .ok_or(UserError::NotFound(user_id))?;
+
+        let changes = Self::release_seats(&transaction, &[user_id], rules).await?;
+
+        // The rows of the past editions are deleted by the foreign keys
+        user.delete(&transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok(changes)
+    }
+}